@@ -0,0 +1,72 @@
+/// Account updates archived in the `accounts` table.
+use {
+    crate::geyser_plugin_cos_config::AccountsConfig,
+    solana_geyser_plugin_interface::geyser_plugin_interface::{GeyserPluginError, Result},
+    solana_sdk::pubkey::Pubkey,
+    std::{collections::HashSet, str::FromStr},
+};
+
+/// An account write, encoded in the `accounts` table cell of its slot.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AccountUpdate {
+    #[prost(bytes = "vec", tag = "1")]
+    pub pubkey: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub owner: Vec<u8>,
+    #[prost(uint64, tag = "3")]
+    pub lamports: u64,
+    #[prost(bool, tag = "4")]
+    pub executable: bool,
+    #[prost(uint64, tag = "5")]
+    pub rent_epoch: u64,
+    #[prost(bytes = "vec", tag = "6")]
+    pub data: Vec<u8>,
+    /// Orders the writes to the same account within a slot.
+    #[prost(uint64, tag = "7")]
+    pub write_version: u64,
+    /// Signature of the transaction that caused the write, if any.
+    #[prost(bytes = "vec", optional, tag = "8")]
+    pub txn_signature: Option<Vec<u8>>,
+}
+
+/// The `accounts` table cell of a slot.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AccountUpdates {
+    #[prost(message, repeated, tag = "1")]
+    pub accounts: Vec<AccountUpdate>,
+}
+
+/// Selects the accounts whose updates are archived.
+#[derive(Clone, Debug, Default)]
+pub struct AccountsFilter {
+    owners: HashSet<Pubkey>,
+    pubkeys: HashSet<Pubkey>,
+}
+
+impl AccountsFilter {
+    pub fn new(config: &AccountsConfig) -> Result<Self> {
+        Ok(Self {
+            owners: parse_pubkeys("accounts.owners", &config.owners)?,
+            pubkeys: parse_pubkeys("accounts.pubkeys", &config.pubkeys)?,
+        })
+    }
+
+    /// Returns true if updates to this account are archived. An empty filter selects every
+    /// account.
+    pub fn wants_account(&self, pubkey: &Pubkey, owner: &Pubkey) -> bool {
+        (self.owners.is_empty() && self.pubkeys.is_empty())
+            || self.owners.contains(owner)
+            || self.pubkeys.contains(pubkey)
+    }
+}
+
+pub(crate) fn parse_pubkeys(field: &str, pubkeys: &[String]) -> Result<HashSet<Pubkey>> {
+    pubkeys
+        .iter()
+        .map(|pubkey| {
+            Pubkey::from_str(pubkey).map_err(|err| GeyserPluginError::ConfigFileReadError {
+                msg: format!("COS: Invalid pubkey {pubkey} in {field}: {err}"),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,432 @@
+/// Local admin API, as JSON-RPC 2.0 over HTTP.
+use {
+    crate::{
+        datastore::Datastore,
+        geyser_plugin_cos::save_slots,
+        http::{HttpResponse, HttpServer},
+        storage::StorageManager,
+    },
+    serde_json::{json, Value},
+    solana_sdk::clock::Slot,
+    std::{
+        io,
+        net::SocketAddr,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+    },
+};
+
+/// Slots behind the latest rooted slot that are considered complete, as in `on_slot_rooted`.
+const COMPLETE_SLOT_DELAY: u64 = 10;
+
+/// JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+struct AdminError {
+    code: i64,
+    message: String,
+}
+
+impl AdminError {
+    fn invalid_params(message: &str) -> Self {
+        Self {
+            code: INVALID_PARAMS,
+            message: message.to_string(),
+        }
+    }
+
+    fn internal<E: std::fmt::Display>(err: E) -> Self {
+        Self {
+            code: INTERNAL_ERROR,
+            message: format!("{err}"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CachedSlot {
+    slot: Slot,
+    status: String,
+    transactions: usize,
+    entries: usize,
+    has_block_metadata: bool,
+}
+
+/// Handles admin requests against the state shared with the plugin.
+///
+/// Requests are plain JSON-RPC strings, so the handler can be driven without the HTTP server.
+#[derive(Clone)]
+pub(crate) struct AdminHandler {
+    datastore: Arc<Mutex<Datastore>>,
+    storage: Arc<StorageManager>,
+    paused: Arc<AtomicBool>,
+}
+
+impl AdminHandler {
+    pub fn new(
+        datastore: Arc<Mutex<Datastore>>,
+        storage: Arc<StorageManager>,
+        paused: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            datastore,
+            storage,
+            paused,
+        }
+    }
+
+    /// Handles a JSON-RPC request and returns the JSON-RPC response.
+    pub fn handle(&self, request: &str) -> String {
+        let request: Value = match serde_json::from_str(request) {
+            Ok(request) => request,
+            Err(err) => return Self::error_response(Value::Null, PARSE_ERROR, &err.to_string()),
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            return Self::error_response(id, INVALID_REQUEST, "missing method");
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+        match self.call(method, &params) {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}).to_string(),
+            Err(err) => Self::error_response(id, err.code, &err.message),
+        }
+    }
+
+    fn error_response(id: Value, code: i64, message: &str) -> String {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {"code": code, "message": message},
+        })
+        .to_string()
+    }
+
+    fn call(&self, method: &str, params: &Value) -> Result<Value, AdminError> {
+        match method {
+            "getStatus" => Ok(self.get_status()),
+            "getCachedSlots" => Ok(self.get_cached_slots()),
+            "pause" => {
+                self.paused.store(true, Ordering::Relaxed);
+                log::info!("COS: Writes paused by admin request");
+                Ok(json!({"paused": true}))
+            }
+            "resume" => {
+                self.paused.store(false, Ordering::Relaxed);
+                log::info!("COS: Writes resumed by admin request");
+                self.flush(None)
+            }
+            "flush" => self.flush(Self::param(params, "slot", 0).and_then(Value::as_u64)),
+            "commit" => {
+                let force = match Self::param(params, "force", 0) {
+                    None | Some(Value::Null) => false,
+                    Some(force) => force
+                        .as_bool()
+                        .ok_or_else(|| AdminError::invalid_params("force must be a boolean"))?,
+                };
+                self.commit(force)
+            }
+            "setLogLevel" => {
+                let filter = Self::param(params, "filter", 0)
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| AdminError::invalid_params("missing filter"))?;
+                solana_logger::setup_with(filter);
+                log::info!("COS: Log filter set to {filter} by admin request");
+                Ok(json!({"filter": filter}))
+            }
+            _ => Err(AdminError {
+                code: METHOD_NOT_FOUND,
+                message: format!("unknown method {method}"),
+            }),
+        }
+    }
+
+    /// Parameter passed by name, or by position.
+    fn param<'a>(params: &'a Value, name: &str, position: usize) -> Option<&'a Value> {
+        match params {
+            Value::Object(params) => params.get(name),
+            Value::Array(params) => params.get(position),
+            _ => None,
+        }
+    }
+
+    fn get_status(&self) -> Value {
+        let datastore = self.datastore.lock().unwrap();
+        json!({
+            "paused": self.paused.load(Ordering::Relaxed),
+            "first_slot": datastore.first_slot(),
+            "end_slot": datastore.end_slot(),
+            "capture_complete": datastore.is_complete(),
+            "latest_rooted_slot": datastore.latest_rooted_slot(),
+            "cached_slots": datastore.entries().count(),
+        })
+    }
+
+    fn get_cached_slots(&self) -> Value {
+        let datastore = self.datastore.lock().unwrap();
+        let mut slots: Vec<_> = datastore
+            .entries()
+            .map(|(slot, block_with_entries)| CachedSlot {
+                slot: *slot,
+                status: format!("{:?}", block_with_entries.slot_status),
+                transactions: block_with_entries.block.transactions.len(),
+                entries: block_with_entries.entries.len(),
+                has_block_metadata: !block_with_entries.block.blockhash.is_empty(),
+            })
+            .collect();
+        slots.sort_by_key(|slot| slot.slot);
+        json!(slots)
+    }
+
+    /// Moves the completed ranges from staging to storage. With `force`, the range being
+    /// written is committed as well: under the size policy it is closed and the next slot
+    /// starts a new range, while under the other policies the slots saved to it afterwards
+    /// are added to the committed range when it is committed again.
+    fn commit(&self, force: bool) -> Result<Value, AdminError> {
+        if force {
+            self.storage.commit_all()
+        } else {
+            self.storage.commit_completed()
+        }
+        .map_err(AdminError::internal)?;
+        log::info!("COS: Ranges committed by admin request (force = {force})");
+        Ok(json!({"committed": true, "force": force}))
+    }
+
+    /// Saves the rooted slots of the cache up to `last_slot`, by default the slots that are
+    /// complete, and discards the other slots up to it. A `last_slot` after the complete
+    /// slots is rejected, as its slots may be unrooted or still be notified.
+    fn flush(&self, last_slot: Option<Slot>) -> Result<Value, AdminError> {
+        let slots: Vec<Slot> = {
+            let datastore = self.datastore.lock().unwrap();
+            let complete_slot = datastore
+                .latest_rooted_slot()
+                .map(|slot| slot.saturating_sub(COMPLETE_SLOT_DELAY));
+            let Some(last_slot) = last_slot.or(complete_slot) else {
+                return Ok(json!({"saved": 0, "discarded": 0}));
+            };
+            match complete_slot {
+                None => return Err(AdminError::invalid_params("no slot is rooted yet")),
+                Some(complete_slot) if last_slot > complete_slot => {
+                    return Err(AdminError::invalid_params(&format!(
+                        "slot {last_slot} may be incomplete, flush up to slot {complete_slot}"
+                    )));
+                }
+                _ => {}
+            }
+            let mut slots: Vec<Slot> = datastore
+                .entries()
+                .map(|(slot, _)| *slot)
+                .filter(|slot| *slot <= last_slot)
+                .collect();
+            slots.sort_unstable();
+            slots
+        };
+        let summary =
+            save_slots(&self.datastore, &self.storage, slots).map_err(AdminError::internal)?;
+        log::info!("COS: Flushed {summary:?} by admin request");
+        Ok(json!(summary))
+    }
+}
+
+/// HTTP server accepting JSON-RPC requests on `POST /`. Stopped when dropped.
+pub(crate) struct AdminServer {
+    _server: HttpServer,
+}
+
+impl AdminServer {
+    pub fn start(address: SocketAddr, handler: AdminHandler) -> io::Result<Self> {
+        if !address.ip().is_loopback() {
+            log::warn!("COS: The admin API is listening on non-local address {address}");
+        }
+        let server = HttpServer::start("cosAdmin", address, move |request| {
+            if request.method != "POST" || request.path != "/" {
+                return HttpResponse::not_found();
+            }
+            HttpResponse {
+                status: "200 OK",
+                content_type: "application/json",
+                body: handler
+                    .handle(&String::from_utf8_lossy(&request.body))
+                    .into_bytes(),
+            }
+        })?;
+        log::info!(
+            "COS: Serving admin API on http://{}/",
+            server.local_address()
+        );
+        Ok(Self { _server: server })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*, crate::geyser_plugin_cos_config::GeyserPluginCosConfig,
+        solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+        solana_sdk::hash::Hash, std::path::PathBuf,
+    };
+
+    /// A handler over an empty workspace with ranges of 10 slots.
+    fn setup(name: &str) -> (AdminHandler, PathBuf) {
+        let workspace =
+            std::env::temp_dir().join(format!("cos-admin-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&workspace);
+        let config: GeyserPluginCosConfig = serde_json::from_value(json!({
+            "workspace": workspace,
+            "genesis_hash": Hash::default().to_string(),
+            "slot_range": 10,
+            "commit_slot_delay": 5,
+        }))
+        .unwrap();
+        let handler = AdminHandler::new(
+            Arc::new(Mutex::new(Datastore::new(&config))),
+            Arc::new(StorageManager::new(&config).unwrap()),
+            Arc::new(AtomicBool::new(false)),
+        );
+        (handler, workspace)
+    }
+
+    fn root_slots(handler: &AdminHandler, slots: impl IntoIterator<Item = Slot>) {
+        let mut datastore = handler.datastore.lock().unwrap();
+        for slot in slots {
+            datastore.get_mut_entry(slot).slot_status = SlotStatus::Rooted;
+            datastore.set_rooted(slot);
+        }
+    }
+
+    fn call(handler: &AdminHandler, request: Value) -> Value {
+        serde_json::from_str(&handler.handle(&request.to_string())).unwrap()
+    }
+
+    fn range_folders(path: PathBuf) -> usize {
+        std::fs::read_dir(path)
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().is_dir())
+            .count()
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        let (handler, workspace) = setup("invalid");
+        let response: Value = serde_json::from_str(&handler.handle("{")).unwrap();
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+        let response = call(&handler, json!({"jsonrpc": "2.0", "id": 1}));
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+        let response = call(
+            &handler,
+            json!({"jsonrpc": "2.0", "id": 2, "method": "reboot"}),
+        );
+        assert_eq!(response["id"], 2);
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        std::fs::remove_dir_all(workspace).unwrap();
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        let (handler, workspace) = setup("pause");
+        root_slots(&handler, 10..=30);
+        let response = call(
+            &handler,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "pause"}),
+        );
+        assert_eq!(response["result"]["paused"], true);
+        let response = call(
+            &handler,
+            json!({"jsonrpc": "2.0", "id": 2, "method": "getStatus"}),
+        );
+        assert_eq!(response["result"]["paused"], true);
+        assert_eq!(response["result"]["cached_slots"], 21);
+
+        let response = call(
+            &handler,
+            json!({"jsonrpc": "2.0", "id": 3, "method": "resume"}),
+        );
+        assert_eq!(response["result"]["saved"], 11);
+        let response = call(
+            &handler,
+            json!({"jsonrpc": "2.0", "id": 4, "method": "getStatus"}),
+        );
+        assert_eq!(response["result"]["paused"], false);
+        assert_eq!(response["result"]["cached_slots"], 10);
+        std::fs::remove_dir_all(workspace).unwrap();
+    }
+
+    #[test]
+    fn test_flush_rejects_incomplete_slots() {
+        let (handler, workspace) = setup("flush");
+        let response = call(
+            &handler,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "flush"}),
+        );
+        assert_eq!(response["result"]["saved"], 0);
+        let flush = json!({"jsonrpc": "2.0", "id": 2, "method": "flush", "params": {"slot": 12}});
+        assert_eq!(call(&handler, flush)["error"]["code"], INVALID_PARAMS);
+
+        root_slots(&handler, 10..=30);
+        let flush = json!({"jsonrpc": "2.0", "id": 3, "method": "flush", "params": [25]});
+        assert_eq!(call(&handler, flush)["error"]["code"], INVALID_PARAMS);
+        let flush = json!({"jsonrpc": "2.0", "id": 4, "method": "flush", "params": [12]});
+        assert_eq!(call(&handler, flush)["result"]["saved"], 3);
+        std::fs::remove_dir_all(workspace).unwrap();
+    }
+
+    #[test]
+    fn test_commit_keeps_the_range_being_written() {
+        let (handler, workspace) = setup("commit");
+        root_slots(&handler, (10..=14).chain(20..=22).chain([40]));
+        let response = call(
+            &handler,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "flush"}),
+        );
+        assert_eq!(response["result"]["saved"], 8);
+        assert_eq!(range_folders(workspace.join("staging")), 2);
+
+        let response = call(
+            &handler,
+            json!({"jsonrpc": "2.0", "id": 2, "method": "commit"}),
+        );
+        assert_eq!(response["result"]["committed"], true);
+        assert_eq!(range_folders(workspace.join("storage")), 1);
+        assert_eq!(range_folders(workspace.join("staging")), 1);
+
+        let commit = json!({"jsonrpc": "2.0", "id": 3, "method": "commit", "params": ["yes"]});
+        assert_eq!(call(&handler, commit)["error"]["code"], INVALID_PARAMS);
+        assert_eq!(range_folders(workspace.join("staging")), 1);
+        std::fs::remove_dir_all(workspace).unwrap();
+    }
+
+    #[test]
+    fn test_force_commit() {
+        let (handler, workspace) = setup("force-commit");
+        root_slots(&handler, (10..=14).chain(20..=22).chain([40]));
+        call(
+            &handler,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "flush"}),
+        );
+        let commit =
+            json!({"jsonrpc": "2.0", "id": 2, "method": "commit", "params": {"force": true}});
+        let response = call(&handler, commit);
+        assert_eq!(response["result"]["force"], true);
+        assert_eq!(range_folders(workspace.join("storage")), 2);
+        assert_eq!(range_folders(workspace.join("staging")), 0);
+
+        // The slots saved to the range afterwards are committed with it.
+        root_slots(&handler, 23..=50);
+        let flush = json!({"jsonrpc": "2.0", "id": 3, "method": "flush", "params": [24]});
+        assert_eq!(call(&handler, flush)["result"]["saved"], 2);
+        call(
+            &handler,
+            json!({"jsonrpc": "2.0", "id": 4, "method": "commit", "params": [true]}),
+        );
+        let range_path = workspace.join("storage/range_0000000000000014_000000000000001e");
+        assert_eq!(range_folders(range_path), 5);
+        std::fs::remove_dir_all(workspace).unwrap();
+    }
+}
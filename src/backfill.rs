@@ -0,0 +1,115 @@
+/// Offline backfill of slot ranges, for slots the plugin did not see.
+use {
+    crate::{
+        cos_types::CosVersionedConfirmedBlockWithEntries,
+        geyser_plugin_cos_config::GeyserPluginCosConfig,
+        storage::{Storage, StorageManager},
+    },
+    solana_sdk::{clock::Slot, hash::Hash},
+    std::{
+        io::{self, ErrorKind},
+        thread,
+    },
+};
+
+/// A source of rooted blocks.
+pub(crate) trait BlockSource: Sync {
+    /// Returns the block of a rooted slot, or `None` if the slot was skipped.
+    fn get_block(&self, slot: Slot) -> io::Result<Option<CosVersionedConfirmedBlockWithEntries>>;
+
+    /// Returns the genesis hash of the cluster of the source.
+    fn genesis_hash(&self) -> io::Result<Hash>;
+}
+
+#[derive(Debug, Default)]
+pub struct BackfillSummary {
+    /// Number of slots saved to storage.
+    pub saved: u64,
+    /// Number of slots that were skipped or are not rooted.
+    pub skipped: u64,
+    /// Number of slots that could not be read from the source.
+    pub failed: u64,
+}
+
+/// Save the rooted blocks between `first_slot` and `last_slot` (inclusive) to the workspace
+/// of `config`, then commit every range that was written.
+///
+/// Up to `concurrency` blocks are read from the source at the same time, but they are always
+/// saved in slot order, as the plugin does.
+///
+/// The workspace must not be used by a running plugin, and its staging folder must be empty:
+/// the slots a plugin saved there are never discarded. The genesis hash of the source must be
+/// the configured one, if any, and it is recorded in the workspace, which refuses the blocks
+/// of another cluster.
+pub(crate) fn backfill(
+    source: &dyn BlockSource,
+    config: &GeyserPluginCosConfig,
+    first_slot: Slot,
+    last_slot: Slot,
+    concurrency: usize,
+) -> io::Result<BackfillSummary> {
+    let mut config = config.clone();
+    let genesis_hash = source.genesis_hash()?.to_string();
+    if let Some(configured) = config
+        .genesis_hash
+        .as_ref()
+        .filter(|configured| **configured != genesis_hash)
+    {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "the source is on the cluster of genesis hash {genesis_hash}, not the configured \
+                 {configured}"
+            ),
+        ));
+    }
+    config.genesis_hash = Some(genesis_hash);
+    config
+        .resolve_cluster()
+        .and_then(|()| config.validate())
+        .map_err(|err| io::Error::new(ErrorKind::InvalidInput, err.to_string()))?;
+
+    let storage = StorageManager::new_offline(&config)?;
+    let mut summary = BackfillSummary::default();
+    let batch_size = concurrency.max(1) as u64;
+
+    let mut batch_first_slot = first_slot;
+    while batch_first_slot <= last_slot {
+        let batch_last_slot = last_slot.min(batch_first_slot.saturating_add(batch_size - 1));
+        let blocks: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = (batch_first_slot..=batch_last_slot)
+                .map(|slot| scope.spawn(move || (slot, source.get_block(slot))))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        for (slot, block) in blocks {
+            match block {
+                Ok(Some(block_with_entries)) => {
+                    log::debug!("COS: Saving slot {slot} to storage");
+                    storage.save(slot, &block_with_entries)?;
+                    summary.saved += 1;
+                }
+                Ok(None) => {
+                    log::debug!("COS: Slot {slot} is not rooted, skipping");
+                    summary.skipped += 1;
+                }
+                Err(err) => {
+                    log::warn!("COS: Failed to read slot {slot}: {err}");
+                    summary.failed += 1;
+                }
+            }
+        }
+
+        if batch_last_slot == Slot::MAX {
+            break;
+        }
+        batch_first_slot = batch_last_slot + 1;
+    }
+
+    storage.commit_all()?;
+    Ok(summary)
+}
@@ -0,0 +1,137 @@
+/// Backfill source reading rooted blocks from a local validator ledger.
+use {
+    crate::{
+        backfill::{backfill, BackfillSummary, BlockSource},
+        cluster::ledger_genesis_hash,
+        cos_types::CosVersionedConfirmedBlockWithEntries,
+        geyser_plugin_cos_config::GeyserPluginCosConfig,
+    },
+    solana_ledger::{
+        blockstore::Blockstore,
+        blockstore_options::{AccessType, BlockstoreOptions},
+    },
+    solana_sdk::{clock::Slot, hash::Hash},
+    std::{
+        io::{self, ErrorKind},
+        path::Path,
+    },
+};
+
+pub(crate) struct LedgerBlockSource {
+    blockstore: Blockstore,
+}
+
+impl LedgerBlockSource {
+    /// Opens the blockstore with secondary access, so that the ledger of a running
+    /// validator can be read.
+    pub(crate) fn open(ledger_path: &Path) -> io::Result<Self> {
+        let blockstore = Blockstore::open_with_options(
+            ledger_path,
+            BlockstoreOptions {
+                access_type: AccessType::Secondary,
+                ..BlockstoreOptions::default()
+            },
+        )
+        .map_err(|err| {
+            io::Error::new(
+                ErrorKind::Other,
+                format!("Failed to open ledger {}: {err}", ledger_path.display()),
+            )
+        })?;
+        Ok(Self { blockstore })
+    }
+}
+
+impl BlockSource for LedgerBlockSource {
+    fn genesis_hash(&self) -> io::Result<Hash> {
+        ledger_genesis_hash(self.blockstore.ledger_path())
+    }
+
+    fn get_block(&self, slot: Slot) -> io::Result<Option<CosVersionedConfirmedBlockWithEntries>> {
+        if !self.blockstore.is_root(slot) {
+            return Ok(None);
+        }
+        let block_with_entries = self
+            .blockstore
+            .get_rooted_block_with_entries(slot, true)
+            .map_err(|err| io::Error::new(ErrorKind::Other, format!("{err}")))?;
+        Ok(Some(block_with_entries.into()))
+    }
+}
+
+/// Backfill the rooted slots between `first_slot` and `last_slot` (inclusive) from the
+/// ledger at `ledger_path`.
+pub fn backfill_from_ledger(
+    config: &GeyserPluginCosConfig,
+    ledger_path: &Path,
+    first_slot: Slot,
+    last_slot: Slot,
+) -> io::Result<BackfillSummary> {
+    let source = LedgerBlockSource::open(ledger_path)?;
+    backfill(&source, config, first_slot, last_slot, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::reader::WorkspaceReader,
+        serde_json::json,
+        solana_ledger::{
+            blockstore::{create_new_ledger, make_slot_entries},
+            blockstore_options::LedgerColumnOptions,
+        },
+        solana_sdk::genesis_config::GenesisConfig,
+    };
+
+    /// Creates a ledger with slots 1 to 3 after genesis, of which only 1 and 3 are rooted.
+    /// Returns the genesis hash and the blockhash of each slot.
+    fn create_ledger(ledger_path: &Path) -> (Hash, Vec<Hash>) {
+        let genesis_config = GenesisConfig::default();
+        create_new_ledger(
+            ledger_path,
+            &genesis_config,
+            10 * 1024 * 1024,
+            LedgerColumnOptions::default(),
+        )
+        .unwrap();
+        let blockstore = Blockstore::open(ledger_path).unwrap();
+        let mut blockhashes = vec![];
+        for slot in 1..=3 {
+            let (shreds, entries) = make_slot_entries(slot, slot - 1, 4, true);
+            blockstore.insert_shreds(shreds, None, false).unwrap();
+            blockhashes.push(entries.last().unwrap().hash);
+        }
+        blockstore.set_roots([1, 3].iter()).unwrap();
+        (genesis_config.hash(), blockhashes)
+    }
+
+    #[test]
+    fn test_backfill_from_ledger_fixture() {
+        let root = std::env::temp_dir().join(format!("cos-backfill-ledger-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let ledger_path = root.join("ledger");
+        let workspace = root.join("workspace");
+        let (genesis_hash, blockhashes) = create_ledger(&ledger_path);
+        let config: GeyserPluginCosConfig = serde_json::from_value(json!({
+            "workspace": workspace,
+            "slot_range": 10,
+            "commit_slot_delay": 5,
+        }))
+        .unwrap();
+
+        let summary = backfill_from_ledger(&config, &ledger_path, 1, 3).unwrap();
+        assert_eq!((summary.saved, summary.skipped, summary.failed), (2, 1, 0));
+
+        let reader = WorkspaceReader::open(&workspace).unwrap();
+        let layout = reader.layout().unwrap();
+        assert_eq!(layout.genesis_hash, Some(genesis_hash.to_string()));
+        let slots = reader.all_slots().unwrap();
+        assert_eq!(slots.keys().copied().collect::<Vec<_>>(), vec![1, 3]);
+        let block = WorkspaceReader::read_block(&slots[&3]).unwrap().unwrap();
+        assert_eq!(block.blockhash, blockhashes[2].to_string());
+        assert_eq!(block.previous_blockhash, blockhashes[1].to_string());
+        assert_eq!(block.parent_slot, 2);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
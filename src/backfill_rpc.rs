@@ -0,0 +1,342 @@
+/// Backfill source fetching rooted blocks from a JSON-RPC endpoint.
+use {
+    crate::{
+        backfill::{backfill, BackfillSummary, BlockSource},
+        cos_types::CosVersionedConfirmedBlockWithEntries,
+        geyser_plugin_cos_config::GeyserPluginCosConfig,
+    },
+    solana_rpc_client::rpc_client::RpcClient,
+    solana_rpc_client_api::{
+        client_error::{Error as ClientError, ErrorKind as ClientErrorKind},
+        config::RpcBlockConfig,
+        custom_error::{
+            JSON_RPC_SERVER_ERROR_BLOCK_CLEANED_UP,
+            JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED,
+            JSON_RPC_SERVER_ERROR_SLOT_SKIPPED,
+        },
+        request::RpcError,
+    },
+    solana_sdk::{
+        clock::Slot, commitment_config::CommitmentConfig, hash::Hash, message::v0::LoadedAddresses,
+        pubkey::Pubkey,
+    },
+    solana_transaction_status::{
+        EncodedTransactionWithStatusMeta, TransactionDetails, TransactionStatusMeta,
+        UiConfirmedBlock, UiLoadedAddresses, UiTransactionEncoding, VersionedConfirmedBlock,
+        VersionedConfirmedBlockWithEntries, VersionedTransactionWithStatusMeta,
+    },
+    std::{
+        io::{self, ErrorKind},
+        str::FromStr,
+        thread::sleep,
+        time::Duration,
+    },
+};
+
+/// Longest delay between two retries of a request.
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Settings of the JSON-RPC backfill source.
+#[derive(Clone, Debug)]
+pub struct RpcBackfillOptions {
+    /// URL of the JSON-RPC endpoint.
+    pub url: String,
+    /// Timeout of a single `getBlock` request.
+    pub timeout: Duration,
+    /// Maximum number of `getBlock` requests in flight.
+    pub concurrency: usize,
+    /// Number of times a failed request is retried.
+    pub max_retries: usize,
+    /// Delay before the first retry, doubled on every further retry up to
+    /// `MAX_RETRY_DELAY`.
+    pub retry_delay: Duration,
+}
+
+impl Default for RpcBackfillOptions {
+    fn default() -> Self {
+        Self {
+            url: "http://127.0.0.1:8899".to_string(),
+            timeout: Duration::from_secs(30),
+            concurrency: 4,
+            max_retries: 5,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+pub(crate) struct RpcBlockSource {
+    client: RpcClient,
+    max_retries: usize,
+    retry_delay: Duration,
+}
+
+impl RpcBlockSource {
+    pub(crate) fn new(options: &RpcBackfillOptions) -> Self {
+        Self {
+            client: RpcClient::new_with_timeout(options.url.clone(), options.timeout),
+            max_retries: options.max_retries,
+            retry_delay: options.retry_delay,
+        }
+    }
+
+    fn get_block_with_retries(&self, slot: Slot) -> io::Result<Option<UiConfirmedBlock>> {
+        let config = RpcBlockConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            transaction_details: Some(TransactionDetails::Full),
+            rewards: Some(true),
+            commitment: Some(CommitmentConfig::finalized()),
+            max_supported_transaction_version: Some(0),
+        };
+
+        let mut retry_delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            match self.client.get_block_with_config(slot, config) {
+                Ok(block) => return Ok(Some(block)),
+                Err(err) => match rpc_error_code(&err) {
+                    Some(JSON_RPC_SERVER_ERROR_SLOT_SKIPPED)
+                    | Some(JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED) => {
+                        return Ok(None)
+                    }
+                    Some(JSON_RPC_SERVER_ERROR_BLOCK_CLEANED_UP) => {
+                        return Err(io::Error::new(ErrorKind::Other, format!("{err}")))
+                    }
+                    _ if attempt < self.max_retries => {
+                        attempt += 1;
+                        log::debug!(
+                            "COS: getBlock {slot} failed ({err}), retry {attempt}/{} in \
+                             {retry_delay:?}",
+                            self.max_retries
+                        );
+                        sleep(retry_delay);
+                        retry_delay = next_retry_delay(retry_delay);
+                    }
+                    _ => return Err(io::Error::new(ErrorKind::Other, format!("{err}"))),
+                },
+            }
+        }
+    }
+}
+
+impl BlockSource for RpcBlockSource {
+    fn genesis_hash(&self) -> io::Result<Hash> {
+        self.client
+            .get_genesis_hash()
+            .map_err(|err| io::Error::new(ErrorKind::Other, format!("getGenesisHash: {err}")))
+    }
+
+    fn get_block(&self, slot: Slot) -> io::Result<Option<CosVersionedConfirmedBlockWithEntries>> {
+        match self.get_block_with_retries(slot)? {
+            Some(block) => Ok(Some(block_from_rpc(block)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Doubles the delay before the next retry, up to `MAX_RETRY_DELAY`.
+fn next_retry_delay(retry_delay: Duration) -> Duration {
+    retry_delay.saturating_mul(2).min(MAX_RETRY_DELAY)
+}
+
+fn rpc_error_code(err: &ClientError) -> Option<i64> {
+    match &err.kind {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => Some(*code),
+        _ => None,
+    }
+}
+
+/// Converts a `getBlock` response to the block assembled from the plugin notifications.
+/// The response has no entries, so only the `blocks` cell is written for the slot.
+fn block_from_rpc(block: UiConfirmedBlock) -> io::Result<CosVersionedConfirmedBlockWithEntries> {
+    let transactions = block
+        .transactions
+        .unwrap_or_default()
+        .into_iter()
+        .map(transaction_from_rpc)
+        .collect::<io::Result<Vec<_>>>()?;
+
+    Ok(VersionedConfirmedBlockWithEntries {
+        block: VersionedConfirmedBlock {
+            previous_blockhash: block.previous_blockhash,
+            blockhash: block.blockhash,
+            parent_slot: block.parent_slot,
+            transactions,
+            rewards: block.rewards.unwrap_or_default(),
+            block_time: block.block_time,
+            block_height: block.block_height,
+        },
+        entries: vec![],
+    }
+    .into())
+}
+
+fn transaction_from_rpc(
+    transaction: EncodedTransactionWithStatusMeta,
+) -> io::Result<VersionedTransactionWithStatusMeta> {
+    let meta = transaction
+        .meta
+        .ok_or_else(|| invalid_data("transaction without status meta".to_string()))?;
+    let versioned_transaction = transaction
+        .transaction
+        .decode()
+        .ok_or_else(|| invalid_data("transaction could not be decoded".to_string()))?;
+    let loaded_addresses = Option::<UiLoadedAddresses>::from(meta.loaded_addresses)
+        .map(|addresses| loaded_addresses_from_rpc(&addresses))
+        .transpose()?
+        .unwrap_or_default();
+
+    Ok(VersionedTransactionWithStatusMeta {
+        transaction: versioned_transaction,
+        meta: TransactionStatusMeta {
+            status: meta.status,
+            loaded_addresses,
+            // Below fields are not used in the context of the COS plugin
+            ..Default::default()
+        },
+    })
+}
+
+fn loaded_addresses_from_rpc(addresses: &UiLoadedAddresses) -> io::Result<LoadedAddresses> {
+    let parse = |keys: &[String]| {
+        keys.iter()
+            .map(|key| Pubkey::from_str(key).map_err(|err| invalid_data(format!("{key}: {err}"))))
+            .collect::<io::Result<Vec<_>>>()
+    };
+    Ok(LoadedAddresses {
+        writable: parse(&addresses.writable)?,
+        readonly: parse(&addresses.readonly)?,
+    })
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// Backfill the rooted slots between `first_slot` and `last_slot` (inclusive) from the
+/// JSON-RPC endpoint in `options`.
+pub fn backfill_from_rpc(
+    config: &GeyserPluginCosConfig,
+    options: &RpcBackfillOptions,
+    first_slot: Slot,
+    last_slot: Slot,
+) -> io::Result<BackfillSummary> {
+    let source = RpcBlockSource::new(options);
+    backfill(&source, config, first_slot, last_slot, options.concurrency)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            http::{HttpResponse, HttpServer},
+            reader::WorkspaceReader,
+        },
+        serde_json::{json, Value},
+        std::{
+            collections::HashMap,
+            sync::{Arc, Mutex},
+        },
+    };
+
+    /// Serves `getVersion`, `getGenesisHash`, and `getBlock` for slots 10 to 13: slot 12 is
+    /// skipped, and the first request for slot 13 fails. Returns the number of `getBlock`
+    /// requests per slot.
+    fn start_mock_rpc(genesis_hash: Hash) -> (HttpServer, Arc<Mutex<HashMap<u64, usize>>>) {
+        let requests = Arc::new(Mutex::new(HashMap::new()));
+        let server_requests = requests.clone();
+        let server = HttpServer::start("mockRpc", "127.0.0.1:0".parse().unwrap(), move |request| {
+            let request: Value = serde_json::from_slice(&request.body).unwrap();
+            let id = request["id"].clone();
+            let response = match request["method"].as_str().unwrap() {
+                "getVersion" => json!({"result": {"solana-core": "1.18.22", "feature-set": 0}}),
+                "getGenesisHash" => json!({"result": genesis_hash.to_string()}),
+                "getBlock" => {
+                    let slot = request["params"][0].as_u64().unwrap();
+                    let attempts = {
+                        let mut requests = server_requests.lock().unwrap();
+                        let attempts = requests.entry(slot).or_insert(0);
+                        *attempts += 1;
+                        *attempts
+                    };
+                    match slot {
+                        12 => json!({"error": {
+                            "code": JSON_RPC_SERVER_ERROR_SLOT_SKIPPED,
+                            "message": "Slot 12 was skipped",
+                        }}),
+                        13 if attempts == 1 => json!({"error": {
+                            "code": -32603,
+                            "message": "Internal error",
+                        }}),
+                        _ => json!({"result": {
+                            "previousBlockhash": Hash::new_from_array([slot as u8 - 1; 32])
+                                .to_string(),
+                            "blockhash": Hash::new_from_array([slot as u8; 32]).to_string(),
+                            "parentSlot": slot - 1,
+                            "transactions": [],
+                            "rewards": [],
+                            "blockTime": 1_700_000_000 + slot as i64,
+                            "blockHeight": slot,
+                        }}),
+                    }
+                }
+                method => panic!("unexpected method {method}"),
+            };
+            let mut response = response.as_object().unwrap().clone();
+            response.insert("jsonrpc".to_string(), json!("2.0"));
+            response.insert("id".to_string(), id);
+            HttpResponse {
+                status: "200 OK",
+                content_type: "application/json",
+                body: serde_json::to_vec(&response).unwrap(),
+            }
+        })
+        .unwrap();
+        (server, requests)
+    }
+
+    #[test]
+    fn test_backfill_from_mock_rpc() {
+        let genesis_hash = Hash::new_unique();
+        let (server, requests) = start_mock_rpc(genesis_hash);
+        let workspace =
+            std::env::temp_dir().join(format!("cos-backfill-rpc-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&workspace);
+        let config: GeyserPluginCosConfig = serde_json::from_value(json!({
+            "workspace": workspace,
+            "slot_range": 10,
+            "commit_slot_delay": 5,
+        }))
+        .unwrap();
+        let options = RpcBackfillOptions {
+            url: format!("http://{}", server.local_address()),
+            concurrency: 2,
+            retry_delay: Duration::from_millis(1),
+            ..Default::default()
+        };
+
+        let summary = backfill_from_rpc(&config, &options, 10, 13).unwrap();
+        assert_eq!((summary.saved, summary.skipped, summary.failed), (3, 1, 0));
+        assert_eq!(requests.lock().unwrap()[&13], 2);
+
+        let reader = WorkspaceReader::open(&workspace).unwrap();
+        let layout = reader.layout().unwrap();
+        assert_eq!(layout.genesis_hash, Some(genesis_hash.to_string()));
+        let slots = reader.all_slots().unwrap();
+        assert_eq!(slots.keys().copied().collect::<Vec<_>>(), vec![10, 11, 13]);
+        let block = WorkspaceReader::read_block(&slots[&13]).unwrap().unwrap();
+        assert_eq!(block.blockhash, Hash::new_from_array([13; 32]).to_string());
+        assert_eq!(block.parent_slot, 12);
+        std::fs::remove_dir_all(workspace).unwrap();
+    }
+
+    #[test]
+    fn test_retry_delay_is_capped() {
+        assert_eq!(
+            next_retry_delay(Duration::from_millis(500)),
+            Duration::from_secs(1)
+        );
+        assert_eq!(next_retry_delay(Duration::from_secs(40)), MAX_RETRY_DELAY);
+        assert_eq!(next_retry_delay(Duration::MAX), MAX_RETRY_DELAY);
+    }
+}
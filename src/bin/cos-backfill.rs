@@ -0,0 +1,126 @@
+/// Fill gaps in the slot ranges written by the COS plugin.
+use {
+    clap::{Parser, Subcommand},
+    solana_cos_plugin::{
+        backfill::BackfillSummary,
+        backfill_rpc::{backfill_from_rpc, RpcBackfillOptions},
+        geyser_plugin_cos_config::GeyserPluginCosConfig,
+    },
+    solana_sdk::clock::Slot,
+    std::{process::ExitCode, time::Duration},
+};
+
+#[cfg(feature = "ledger")]
+use {solana_cos_plugin::backfill_ledger::backfill_from_ledger, std::path::PathBuf};
+
+#[derive(Parser)]
+#[command(name = "cos-backfill", version, about)]
+struct Cli {
+    /// Plugin config file, used for the range layout.
+    #[arg(short, long, default_value = "./config.json")]
+    config: String,
+
+    /// Workspace to write to, instead of the one from the config file. It must not be used by
+    /// a running plugin, and the backfill refuses to start while its staging folder holds
+    /// slots a plugin did not commit.
+    #[arg(short, long)]
+    workspace: Option<String>,
+
+    /// First slot to backfill.
+    #[arg(long)]
+    start_slot: Slot,
+
+    /// Last slot to backfill (inclusive).
+    #[arg(long)]
+    end_slot: Slot,
+
+    #[command(subcommand)]
+    source: Source,
+}
+
+#[derive(Subcommand)]
+enum Source {
+    /// Fetch finalized blocks with `getBlock` from a JSON-RPC endpoint.
+    Rpc {
+        /// URL of the JSON-RPC endpoint.
+        #[arg(short, long, default_value = "http://127.0.0.1:8899")]
+        url: String,
+        /// Maximum number of requests in flight.
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+        /// Number of times a failed request is retried.
+        #[arg(long, default_value_t = 5)]
+        max_retries: usize,
+        /// Delay in milliseconds before the first retry, doubled on every further retry up
+        /// to a minute.
+        #[arg(long, default_value_t = 500)]
+        retry_delay_ms: u64,
+        /// Timeout in seconds of a single request.
+        #[arg(long, default_value_t = 30)]
+        timeout_secs: u64,
+    },
+    /// Read rooted blocks from a local validator ledger.
+    #[cfg(feature = "ledger")]
+    Ledger {
+        /// Path of the validator `ledger` directory.
+        #[arg(short, long)]
+        ledger: PathBuf,
+    },
+}
+
+fn main() -> ExitCode {
+    solana_logger::setup_with_default("info");
+    let cli = Cli::parse();
+
+    let mut config = match GeyserPluginCosConfig::load(&cli.config) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("cos-backfill: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(workspace) = cli.workspace {
+        config.workspace = workspace;
+    }
+
+    let result = match cli.source {
+        Source::Rpc {
+            url,
+            concurrency,
+            max_retries,
+            retry_delay_ms,
+            timeout_secs,
+        } => {
+            let options = RpcBackfillOptions {
+                url,
+                timeout: Duration::from_secs(timeout_secs),
+                concurrency,
+                max_retries,
+                retry_delay: Duration::from_millis(retry_delay_ms),
+            };
+            backfill_from_rpc(&config, &options, cli.start_slot, cli.end_slot)
+        }
+        #[cfg(feature = "ledger")]
+        Source::Ledger { ledger } => {
+            backfill_from_ledger(&config, &ledger, cli.start_slot, cli.end_slot)
+        }
+    };
+    match result {
+        Ok(BackfillSummary {
+            saved,
+            skipped,
+            failed,
+        }) => {
+            println!("saved {saved} slot(s), skipped {skipped}, failed {failed}");
+            if failed > 0 {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        Err(err) => {
+            eprintln!("cos-backfill: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
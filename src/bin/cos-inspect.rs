@@ -0,0 +1,90 @@
+/// Browse and verify the slot ranges written by the COS plugin.
+use {
+    clap::{Parser, Subcommand},
+    solana_cos_plugin::{inspect, reader::WorkspaceReader},
+    solana_sdk::clock::Slot,
+    std::{io, path::PathBuf, process::ExitCode},
+};
+
+#[derive(Parser)]
+#[command(name = "cos-inspect", version, about)]
+struct Cli {
+    /// Plugin workspace, or a folder holding range folders.
+    #[arg(short, long, default_value = "./workspace")]
+    workspace: PathBuf,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the slot ranges in storage and staging.
+    Ranges,
+    /// List the slots of the range containing SLOT.
+    Slots { slot: Slot },
+    /// Print the decoded blocks cell of SLOT as JSON.
+    Block { slot: Slot },
+    /// Print the decoded entries cell of SLOT as JSON.
+    Entries { slot: Slot },
+    /// Print the decoded accounts cell of SLOT as JSON.
+    Accounts { slot: Slot },
+    /// List the startup account snapshots.
+    Snapshots,
+    /// Print compression statistics per table and method.
+    Stats,
+    /// Check that every cell decodes and report missing or extra slots.
+    Verify,
+    /// Compare the decoded cells with another workspace or a reference export.
+    Diff {
+        /// Workspace, or folder holding range folders, to compare with.
+        other: PathBuf,
+        /// First slot to compare.
+        #[arg(long, default_value_t = 0)]
+        start_slot: Slot,
+        /// Last slot to compare (inclusive).
+        #[arg(long, default_value_t = Slot::MAX)]
+        end_slot: Slot,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("cos-inspect: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> io::Result<ExitCode> {
+    let reader = WorkspaceReader::open(&cli.workspace)?;
+    let mut out = io::stdout().lock();
+    match cli.command {
+        Command::Ranges => inspect::list_ranges(&reader, &mut out)?,
+        Command::Slots { slot } => inspect::list_slots(&reader, slot, &mut out)?,
+        Command::Block { slot } => inspect::dump_block(&reader, slot, &mut out)?,
+        Command::Entries { slot } => inspect::dump_entries(&reader, slot, &mut out)?,
+        Command::Accounts { slot } => inspect::dump_accounts(&reader, slot, &mut out)?,
+        Command::Snapshots => inspect::list_snapshots(&reader, &mut out)?,
+        Command::Stats => inspect::compression_stats(&reader, &mut out)?,
+        Command::Verify => {
+            if inspect::verify(&reader, &mut out)? > 0 {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Diff {
+            other,
+            start_slot,
+            end_slot,
+        } => {
+            let other = WorkspaceReader::open(&other)?;
+            if inspect::diff(&reader, &other, start_slot, end_slot, &mut out)? > 0 {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,79 @@
+/// Change the range size of the committed ranges of a workspace written by the COS plugin.
+use {
+    clap::Parser,
+    solana_cos_plugin::{
+        layout::{RangePolicy, WorkspaceLayout},
+        rechunk::{rechunk, RechunkSummary},
+    },
+    solana_sdk::epoch_schedule::MINIMUM_SLOTS_PER_EPOCH,
+    std::{path::PathBuf, process::ExitCode},
+};
+
+#[derive(Parser)]
+#[command(name = "cos-rechunk", version, about)]
+struct Cli {
+    /// Workspace to re-chunk. It must not be used by a running plugin or backfill.
+    #[arg(short, long)]
+    workspace: PathBuf,
+
+    /// New number of slots in each range, to set as `slot_range` in the plugin config.
+    #[arg(long)]
+    slot_range: u64,
+
+    /// Align the ranges on the epochs of this length, as the `epoch` range policy does. At
+    /// least 32.
+    #[arg(long)]
+    slots_per_epoch: Option<u64>,
+
+    /// Whether the first epochs are shorter, with `--slots-per-epoch`.
+    #[arg(long, requires = "slots_per_epoch")]
+    warmup: bool,
+}
+
+fn main() -> ExitCode {
+    solana_logger::setup_with_default("info");
+    let cli = Cli::parse();
+
+    if let Some(slots_per_epoch) = cli
+        .slots_per_epoch
+        .filter(|slots_per_epoch| *slots_per_epoch < MINIMUM_SLOTS_PER_EPOCH)
+    {
+        eprintln!(
+            "cos-rechunk: --slots-per-epoch {slots_per_epoch} must be at least \
+             {MINIMUM_SLOTS_PER_EPOCH}"
+        );
+        return ExitCode::FAILURE;
+    }
+    let range_policy = match cli.slots_per_epoch {
+        Some(slots_per_epoch) => RangePolicy::Epoch {
+            slots_per_epoch,
+            warmup: cli.warmup,
+        },
+        None => RangePolicy::Fixed,
+    };
+    // The folders keep the templates and the cluster recorded in the workspace.
+    let recorded = match WorkspaceLayout::load(&cli.workspace) {
+        Ok(recorded) => recorded,
+        Err(err) => {
+            eprintln!("cos-rechunk: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut layout = recorded.unwrap_or_default();
+    layout.slot_range = cli.slot_range;
+    layout.range_policy = range_policy;
+    match rechunk(&cli.workspace, &layout) {
+        Ok(RechunkSummary {
+            slots,
+            old_ranges,
+            new_ranges,
+        }) => {
+            println!("moved {slots} slot(s) from {old_ranges} range(s) to {new_ranges} range(s)");
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("cos-rechunk: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,46 @@
+/// Cluster identity of a workspace, so that the slots of different clusters are never written
+/// to the same workspace.
+use {
+    solana_sdk::{genesis_config::GenesisConfig, hash::Hash},
+    std::{io, path::Path},
+};
+
+/// Names and genesis hashes of the public clusters.
+pub const KNOWN_CLUSTERS: [(&str, &str); 3] = [
+    (
+        "mainnet-beta",
+        "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d",
+    ),
+    ("devnet", "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG"),
+    ("testnet", "4uhcVJyU9pJkvQyS88uRDiswHXSCkY3zQawwpjk2NsNY"),
+];
+
+/// Name of the public cluster created with `genesis_hash`, if any.
+pub fn cluster_name(genesis_hash: &str) -> Option<&'static str> {
+    KNOWN_CLUSTERS
+        .iter()
+        .find(|(_, hash)| *hash == genesis_hash)
+        .map(|(name, _)| *name)
+}
+
+/// Genesis hash of the public cluster named `cluster`, if any.
+pub fn known_genesis_hash(cluster: &str) -> Option<&'static str> {
+    KNOWN_CLUSTERS
+        .iter()
+        .find(|(name, _)| *name == cluster)
+        .map(|(_, hash)| *hash)
+}
+
+/// Genesis hash of the ledger in `ledger_path`, from its `genesis.bin` file.
+pub fn ledger_genesis_hash(ledger_path: &Path) -> io::Result<Hash> {
+    let genesis_config = GenesisConfig::load(ledger_path).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!(
+                "Failed to read the genesis of ledger {}: {err}",
+                ledger_path.display()
+            ),
+        )
+    })?;
+    Ok(genesis_config.hash())
+}
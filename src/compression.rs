@@ -3,10 +3,10 @@
 /// Note that this code is copied from Solana and should be kept in sync with it.
 use {
     enum_iterator::{all, Sequence},
-    std::io::{self, Write},
+    std::io::{self, BufReader, Read, Write},
 };
 
-#[derive(Debug, Serialize, Deserialize, Sequence)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Sequence)]
 pub enum CompressionMethod {
     NoCompression,
     Bzip2,
@@ -14,6 +14,47 @@ pub enum CompressionMethod {
     Zstd,
 }
 
+fn decompress_reader<'a, R: Read + 'a>(
+    method: CompressionMethod,
+    stream: R,
+) -> Result<Box<dyn Read + 'a>, io::Error> {
+    let buf_reader = BufReader::new(stream);
+    let decompress_reader: Box<dyn Read> = match method {
+        CompressionMethod::Bzip2 => Box::new(bzip2::bufread::BzDecoder::new(buf_reader)),
+        CompressionMethod::Gzip => Box::new(flate2::read::GzDecoder::new(buf_reader)),
+        CompressionMethod::Zstd => Box::new(zstd::stream::read::Decoder::new(buf_reader)?),
+        CompressionMethod::NoCompression => Box::new(buf_reader),
+    };
+    Ok(decompress_reader)
+}
+
+/// Returns the compression method recorded in the header of `data`.
+pub fn compression_method(data: &[u8]) -> Result<CompressionMethod, io::Error> {
+    let method_size = bincode::serialized_size(&CompressionMethod::NoCompression).unwrap();
+    if (data.len() as u64) < method_size {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("data len too small: {}", data.len()),
+        ));
+    }
+    bincode::deserialize(&data[..method_size as usize]).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!("method deserialize failed: {err}"),
+        )
+    })
+}
+
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, io::Error> {
+    let method = compression_method(data)?;
+    let method_size = bincode::serialized_size(&method).unwrap();
+
+    let mut reader = decompress_reader(method, &data[method_size as usize..])?;
+    let mut uncompressed_data = vec![];
+    reader.read_to_end(&mut uncompressed_data)?;
+    Ok(uncompressed_data)
+}
+
 pub fn compress(method: CompressionMethod, data: &[u8]) -> Result<Vec<u8>, io::Error> {
     let mut compressed_data = bincode::serialize(&method).unwrap();
     compressed_data.extend(match method {
@@ -38,6 +79,11 @@ pub fn compress(method: CompressionMethod, data: &[u8]) -> Result<Vec<u8>, io::E
     Ok(compressed_data)
 }
 
+/// Compression method of canonical mode, the only one accepted with it.
+/// The bzip2 format and encoder have not changed in years, so its output is stable across
+/// library versions.
+pub const CANONICAL_COMPRESSION_METHOD: CompressionMethod = CompressionMethod::Bzip2;
+
 pub fn compress_best(data: &[u8]) -> Result<Vec<u8>, io::Error> {
     let mut candidates = vec![];
     for method in all::<CompressionMethod>() {
@@ -1,19 +1,25 @@
 use solana_geyser_plugin_interface::geyser_plugin_interface::{
-    ReplicaBlockInfoV3, ReplicaEntryInfoV2, ReplicaTransactionInfoV2,
+    ReplicaAccountInfoV3, ReplicaBlockInfoV3, ReplicaEntryInfoV2, ReplicaTransactionInfoV2,
+    SlotStatus,
 };
 use solana_sdk::{
     hash::Hash,
-    message::{v0::Message, VersionedMessage},
+    message::{v0::Message, AccountKeys, VersionedMessage},
     pubkey::Pubkey,
     transaction::{TransactionError, VersionedTransaction},
 };
 use solana_transaction_status::{
-    EntrySummary, TransactionStatusMeta, VersionedTransactionWithStatusMeta,
+    EntrySummary, TransactionStatusMeta, VersionedConfirmedBlock,
+    VersionedConfirmedBlockWithEntries, VersionedTransactionWithStatusMeta,
 };
 
+use crate::accounts::AccountUpdate;
 use crate::cos_types::{
-    BlockInfoEvent, CosTransactionStatusMeta, CosVersionedTransactionWithStatusMeta, EntryEvent,
+    BlockInfoEvent, CosAccountInfo, CosTransactionStatusMeta,
+    CosVersionedConfirmedBlockWithEntries, CosVersionedTransactionWithStatusMeta, EntryEvent,
 };
+use crate::errors::GeyserPluginCosError;
+use crate::votes::is_simple_vote_transaction;
 
 impl From<&ReplicaBlockInfoV3<'_>> for BlockInfoEvent {
     fn from(block_info: &ReplicaBlockInfoV3) -> Self {
@@ -21,7 +27,7 @@ impl From<&ReplicaBlockInfoV3<'_>> for BlockInfoEvent {
             parent_slot: block_info.parent_slot,
             parent_blockhash: block_info.parent_blockhash.to_string(),
             slot: block_info.slot,
-            blockhash: block_info.parent_blockhash.to_string(),
+            blockhash: block_info.blockhash.to_string(),
             rewards: block_info.rewards.to_vec(),
             block_time: block_info.block_time,
             block_height: block_info.block_height,
@@ -31,6 +37,45 @@ impl From<&ReplicaBlockInfoV3<'_>> for BlockInfoEvent {
     }
 }
 
+/// Fails if the pubkey or owner of the account is not a valid key.
+impl TryFrom<&ReplicaAccountInfoV3<'_>> for CosAccountInfo {
+    type Error = GeyserPluginCosError;
+
+    fn try_from(account_info: &ReplicaAccountInfoV3) -> Result<Self, Self::Error> {
+        let parse_key = |key: &[u8]| {
+            Pubkey::try_from(key)
+                .map_err(|_| GeyserPluginCosError::InvalidAccountKey { len: key.len() })
+        };
+        Ok(CosAccountInfo {
+            pubkey: parse_key(account_info.pubkey)?,
+            owner: parse_key(account_info.owner)?,
+            lamports: account_info.lamports,
+            executable: account_info.executable,
+            rent_epoch: account_info.rent_epoch,
+            data: account_info.data.to_vec(),
+            write_version: account_info.write_version,
+            txn_signature: account_info.txn.map(|txn| *txn.signature()),
+        })
+    }
+}
+
+impl From<CosAccountInfo> for AccountUpdate {
+    fn from(account: CosAccountInfo) -> Self {
+        AccountUpdate {
+            pubkey: account.pubkey.to_bytes().to_vec(),
+            owner: account.owner.to_bytes().to_vec(),
+            lamports: account.lamports,
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data: account.data,
+            write_version: account.write_version,
+            txn_signature: account
+                .txn_signature
+                .map(|signature| signature.as_ref().to_vec()),
+        }
+    }
+}
+
 impl From<&ReplicaEntryInfoV2<'_>> for EntryEvent {
     fn from(entry_info: &ReplicaEntryInfoV2) -> Self {
         EntryEvent {
@@ -87,6 +132,79 @@ impl From<&ReplicaTransactionInfoV2<'_>> for CosVersionedTransactionWithStatusMe
     }
 }
 
+/// Builds the same transaction as the one received through `ReplicaTransactionInfoV2`,
+/// from a transaction read back from a ledger or an RPC node.
+impl From<(usize, VersionedTransactionWithStatusMeta)> for CosVersionedTransactionWithStatusMeta {
+    fn from((index, transaction_with_meta): (usize, VersionedTransactionWithStatusMeta)) -> Self {
+        let VersionedTransactionWithStatusMeta { transaction, meta } = transaction_with_meta;
+        let message = &transaction.message;
+        CosVersionedTransactionWithStatusMeta {
+            transaction: VersionedTransaction {
+                message: VersionedMessage::V0(Message {
+                    header: *message.header(),
+                    account_keys: AccountKeys::new(
+                        message.static_account_keys(),
+                        Some(&meta.loaded_addresses),
+                    )
+                    .iter()
+                    .map(ref_pubkey_to_pubkey)
+                    .collect(),
+                    recent_blockhash: *message.recent_blockhash(),
+                    instructions: message.instructions().to_vec(),
+                    address_table_lookups: message
+                        .address_table_lookups()
+                        .map(|lookups| lookups.to_vec())
+                        .unwrap_or_default(),
+                }),
+                signatures: transaction.signatures,
+            },
+            meta: CosTransactionStatusMeta {
+                status: meta.status.err(),
+                loaded_addresses: meta.loaded_addresses,
+                index,
+            },
+        }
+    }
+}
+
+/// Builds the same block as the one assembled from the plugin notifications of a rooted slot.
+impl From<VersionedConfirmedBlockWithEntries> for CosVersionedConfirmedBlockWithEntries {
+    fn from(block_with_entries: VersionedConfirmedBlockWithEntries) -> Self {
+        let VersionedConfirmedBlockWithEntries { block, entries } = block_with_entries;
+        let executed_transaction_count = block.transactions.len() as u64;
+        let entry_count = entries.len() as u64;
+        let vote_transaction_indexes = block
+            .transactions
+            .iter()
+            .enumerate()
+            .filter(|(_, transaction)| is_simple_vote_transaction(&transaction.transaction))
+            .map(|(index, _)| index)
+            .collect();
+        CosVersionedConfirmedBlockWithEntries {
+            block: VersionedConfirmedBlock {
+                transactions: block
+                    .transactions
+                    .into_iter()
+                    .enumerate()
+                    .map(|transaction| {
+                        CosVersionedTransactionWithStatusMeta::from(transaction).into()
+                    })
+                    .collect(),
+                ..block
+            },
+            entries,
+            accounts: vec![],
+            filtered_transaction_indexes: Default::default(),
+            vote_transaction_indexes,
+            executed_transaction_count,
+            entry_count,
+            slot_status: SlotStatus::Rooted,
+            timings: Default::default(),
+            span: tracing::Span::none(),
+        }
+    }
+}
+
 impl From<EntryEvent> for EntrySummary {
     fn from(entry_event: EntryEvent) -> Self {
         EntrySummary {
@@ -98,6 +216,7 @@ impl From<EntryEvent> for EntrySummary {
     }
 }
 
+/// Copies a pubkey, to map an iterator of references.
 pub fn ref_pubkey_to_pubkey(pubkey: &Pubkey) -> Pubkey {
     *pubkey
 }
@@ -1,12 +1,18 @@
 use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
 use solana_sdk::{
-    clock::{Slot, UnixTimestamp},
+    clock::{Epoch, Slot, UnixTimestamp},
     hash::Hash,
     message::{v0::LoadedAddresses, AccountKeys},
+    pubkey::Pubkey,
+    signature::Signature,
     transaction::{TransactionError, VersionedTransaction},
 };
 use solana_transaction_status::{EntrySummary, Rewards, VersionedConfirmedBlock};
+use std::collections::HashSet;
 
+use crate::timings::SlotTimings;
+
+/// Transaction summary with its memo.
 #[derive(Serialize, Debug)]
 pub struct CosTransactionInfo {
     pub slot: Slot, // The slot that contains the block with this transaction in it
@@ -15,13 +21,18 @@ pub struct CosTransactionInfo {
     pub memo: Option<String>, // Transaction memo
 }
 
+/// The part of the transaction status metadata archived by the plugin.
 #[derive(Debug, Clone)]
 pub struct CosTransactionStatusMeta {
+    /// Error of a failed transaction, `None` if it succeeded.
     pub status: Option<TransactionError>,
     pub loaded_addresses: LoadedAddresses,
+    /// Position of the transaction in the block.
     pub index: usize,
 }
 
+/// A transaction as notified to the plugin. The account keys of the message include the
+/// addresses loaded from lookup tables.
 #[derive(Debug, Clone)]
 pub struct CosVersionedTransactionWithStatusMeta {
     pub transaction: VersionedTransaction,
@@ -29,6 +40,7 @@ pub struct CosVersionedTransactionWithStatusMeta {
 }
 
 impl CosVersionedTransactionWithStatusMeta {
+    /// Static and loaded account keys of the transaction.
     pub fn account_keys(&self) -> AccountKeys {
         AccountKeys::new(
             self.transaction.message.static_account_keys(),
@@ -37,12 +49,41 @@ impl CosVersionedTransactionWithStatusMeta {
     }
 }
 
+/// An account update, as notified to the plugin.
+#[derive(Debug, Clone)]
+pub struct CosAccountInfo {
+    pub pubkey: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub executable: bool,
+    pub rent_epoch: Epoch,
+    pub data: Vec<u8>,
+    /// Global order of the account writes.
+    pub write_version: u64,
+    /// Signature of the transaction that caused the update, if any.
+    pub txn_signature: Option<Signature>,
+}
+
+/// A slot assembled from the plugin notifications, as saved to storage.
+#[non_exhaustive]
 pub struct CosVersionedConfirmedBlockWithEntries {
     pub block: VersionedConfirmedBlock,
     pub entries: Vec<EntrySummary>,
+    pub accounts: Vec<CosAccountInfo>,
+    /// Indexes of the transactions left out by the transaction filter.
+    pub filtered_transaction_indexes: HashSet<usize>,
+    /// Indexes of the vote transactions.
+    pub vote_transaction_indexes: HashSet<usize>,
+    /// Number of transactions executed in the slot, from the block metadata.
     pub executed_transaction_count: u64,
+    /// Number of entries in the slot, from the block metadata.
     pub entry_count: u64,
+    /// Latest status notified for the slot. Only rooted slots are saved.
     pub slot_status: SlotStatus,
+    /// Times at which the slot went through the plugin.
+    pub timings: SlotTimings,
+    /// Span following the slot from its first event until it is saved or discarded.
+    pub span: tracing::Span,
 }
 
 impl Default for CosVersionedConfirmedBlockWithEntries {
@@ -59,19 +100,34 @@ impl Default for CosVersionedConfirmedBlockWithEntries {
                 block_height: Default::default(),
             },
             entries: Default::default(),
+            accounts: Default::default(),
+            filtered_transaction_indexes: Default::default(),
+            vote_transaction_indexes: Default::default(),
             executed_transaction_count: Default::default(),
             entry_count: Default::default(),
             slot_status,
+            timings: Default::default(),
+            span: tracing::Span::none(),
         }
     }
 }
 
+/// A transaction notification.
 #[derive(Debug, Clone)]
 pub struct TransactionEvent {
     pub slot: Slot,
+    pub is_vote: bool,
     pub transaction: CosVersionedTransactionWithStatusMeta,
 }
 
+/// An account update notification.
+#[derive(Debug, Clone)]
+pub struct AccountEvent {
+    pub slot: Slot,
+    pub account: CosAccountInfo,
+}
+
+/// A block metadata notification.
 #[derive(Debug, Clone)]
 pub struct BlockInfoEvent {
     pub parent_slot: Slot,
@@ -85,6 +141,7 @@ pub struct BlockInfoEvent {
     pub entry_count: u64,
 }
 
+/// An entry notification.
 #[derive(Debug, Clone)]
 pub struct EntryEvent {
     pub slot: Slot,
@@ -95,6 +152,9 @@ pub struct EntryEvent {
     pub starting_transaction_index: usize,
 }
 
+/// Key of a cell, e.g. the hexadecimal slot.
 pub type RowKey = String;
+/// Table of a cell, e.g. `blocks`.
 pub type RowType = String;
+/// Compressed protobuf content of a cell.
 pub type RowData = Vec<u8>;
@@ -1,39 +1,79 @@
 use {
     crate::{
         cos_types::CosVersionedConfirmedBlockWithEntries, errors::GeyserPluginCosError,
-        geyser_plugin_cos_config::GeyserPluginCosConfig,
+        geyser_plugin_cos_config::GeyserPluginCosConfig, layout::RangePolicy, metrics::metrics,
+        timings::SlotTimings,
     },
     solana_geyser_plugin_interface::geyser_plugin_interface::{GeyserPluginError, Result},
     solana_sdk::clock::Slot,
     std::collections::HashMap,
 };
 
+/// In memory cache of the slots being notified, until they are saved or discarded.
 #[derive(Default)]
 pub struct Datastore {
     /// In memory cache for finalized slots.
     cache: HashMap<Slot, CosVersionedConfirmedBlockWithEntries>,
     /// First valid slot.
     first_slot: Option<u64>,
+    /// Latest slot notified as rooted.
+    latest_rooted_slot: Option<Slot>,
     /// The number of slots in each range.
     slot_range: u64,
+    /// How slots are grouped into ranges.
+    range_policy: RangePolicy,
+    /// First slot of the capture window, if configured.
+    start_slot: Option<Slot>,
+    /// Last slot of the capture window, if configured.
+    end_slot: Option<Slot>,
+    /// Set once every slot of the capture window is saved and committed.
+    complete: bool,
 }
 
 impl Datastore {
     pub fn new(config: &GeyserPluginCosConfig) -> Self {
         let slot_range = config.slot_range;
         Self {
+            first_slot: config.start_slot,
             slot_range,
+            range_policy: config.range_policy.clone(),
+            start_slot: config.start_slot,
+            end_slot: config.end_slot,
             ..Default::default()
         }
     }
 
+    /// Whether `slot` is in the capture window. Slots outside of it are not cached, and no
+    /// slot is once the capture is complete.
+    pub fn in_window(&self, slot: Slot) -> bool {
+        !self.complete
+            && self
+                .start_slot
+                .map_or(true, |start_slot| slot >= start_slot)
+            && self.end_slot.map_or(true, |end_slot| slot <= end_slot)
+    }
+
+    /// Last slot of the capture window, if configured.
+    pub fn end_slot(&self) -> Option<Slot> {
+        self.end_slot
+    }
+
+    /// Whether every slot of the capture window is saved and committed.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Records that the capture window is complete.
+    pub fn set_complete(&mut self) {
+        self.complete = true;
+    }
+
+    /// Sets the first slot on the first notification, rounded up to the next slot range so
+    /// that only complete ranges are saved, unless `start_slot` is configured. Fails for the
+    /// slots before the first slot.
     pub fn check_first_slot(&mut self, slot: Slot) -> Result<()> {
         if self.first_slot.is_none() {
-            let first_slot = if slot % self.slot_range == 0 {
-                slot
-            } else {
-                slot + (self.slot_range - slot % self.slot_range)
-            };
+            let first_slot = self.range_policy.next_range_start(slot, self.slot_range);
             self.first_slot = Some(first_slot);
             log::info!("Setting first slot to {first_slot}");
         }
@@ -47,11 +87,49 @@ impl Datastore {
         Ok(())
     }
 
+    pub fn first_slot(&self) -> Option<Slot> {
+        self.first_slot
+    }
+
+    pub fn latest_rooted_slot(&self) -> Option<Slot> {
+        self.latest_rooted_slot
+    }
+
+    /// Records the latest rooted slot.
+    pub fn set_rooted(&mut self, slot: Slot) {
+        self.latest_rooted_slot = self.latest_rooted_slot.max(Some(slot));
+    }
+
+    /// Number of slots in the cache.
+    pub fn cache_size(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Cached slots, in no particular order.
+    pub fn entries(&self) -> impl Iterator<Item = (&Slot, &CosVersionedConfirmedBlockWithEntries)> {
+        self.cache.iter()
+    }
+
+    /// The slot in the cache, added if it is not there yet.
     pub fn get_mut_entry(&mut self, slot: Slot) -> &mut CosVersionedConfirmedBlockWithEntries {
-        self.cache.entry(slot).or_default()
+        if !self.cache.contains_key(&slot) {
+            metrics().cache_size.set(self.cache.len() as i64 + 1);
+        }
+        self.cache.entry(slot).or_insert_with(|| {
+            let span = tracing::info_span!("slot", slot);
+            tracing::info!(parent: &span, "First event");
+            CosVersionedConfirmedBlockWithEntries {
+                timings: SlotTimings::new(slot),
+                span,
+                ..Default::default()
+            }
+        })
     }
 
+    /// Removes the slot from the cache.
     pub fn remove_entry(&mut self, slot: Slot) -> Option<CosVersionedConfirmedBlockWithEntries> {
-        self.cache.remove(&slot)
+        let entry = self.cache.remove(&slot);
+        metrics().cache_size.set(self.cache.len() as i64);
+        entry
     }
 }
@@ -1,5 +1,6 @@
 use thiserror::Error;
 
+/// Errors of the plugin, returned to the validator as `GeyserPluginError::Custom`.
 #[derive(Error, Debug)]
 pub enum GeyserPluginCosError {
     #[error("Replica block V0.0.1/v0.0.2 not supported anymore")]
@@ -11,6 +12,12 @@ pub enum GeyserPluginCosError {
     #[error("Replica entry V0.0.1 not supported anymore")]
     ReplicaEntryV001NotSupported,
 
+    #[error("Replica account V0.0.1/V0.0.2 not supported anymore")]
+    ReplicaAccountV001NotSupported,
+
+    #[error("Invalid account key of {len} bytes")]
+    InvalidAccountKey { len: usize },
+
     #[error("Skipping incomplete block range")]
     SkipIncompleteBlockRange,
 
@@ -25,3 +32,22 @@ impl From<std::io::Error> for GeyserPluginCosError {
         }
     }
 }
+
+impl GeyserPluginCosError {
+    /// Name of the variant, used as metrics label.
+    pub fn name(&self) -> &'static str {
+        match self {
+            GeyserPluginCosError::ReplicaBlockV001NotSupported => "ReplicaBlockV001NotSupported",
+            GeyserPluginCosError::ReplicaTransactionV001NotSupported => {
+                "ReplicaTransactionV001NotSupported"
+            }
+            GeyserPluginCosError::ReplicaEntryV001NotSupported => "ReplicaEntryV001NotSupported",
+            GeyserPluginCosError::ReplicaAccountV001NotSupported => {
+                "ReplicaAccountV001NotSupported"
+            }
+            GeyserPluginCosError::InvalidAccountKey { .. } => "InvalidAccountKey",
+            GeyserPluginCosError::SkipIncompleteBlockRange => "SkipIncompleteBlockRange",
+            GeyserPluginCosError::InternalError { .. } => "InternalError",
+        }
+    }
+}
@@ -0,0 +1,251 @@
+/// Filters selecting the transactions that are archived.
+use {
+    crate::{accounts::parse_pubkeys, geyser_plugin_cos_config::TransactionFilterConfig},
+    solana_geyser_plugin_interface::geyser_plugin_interface::Result,
+    solana_sdk::{instruction::CompiledInstruction, message::AccountKeys, pubkey::Pubkey},
+    solana_transaction_status::InnerInstructions,
+    std::collections::HashSet,
+};
+
+/// Number of transactions of a slot, recorded in `transaction_counts.json` next to the
+/// table folders when a transaction filter is configured.
+///
+/// `archived + filtered` is expected to be `executed`: a lower sum means that transaction
+/// notifications were lost, not filtered.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionCounts {
+    /// Number of transactions executed in the slot, from the block metadata.
+    pub executed: u64,
+    /// Number of transactions stored in the `blocks` cell, not counting the empty ones left
+    /// by lost notifications.
+    pub archived: u64,
+    /// Number of transactions left out by the filter.
+    pub filtered: u64,
+}
+
+/// Selects the transactions that are archived.
+#[derive(Clone, Debug, Default)]
+pub struct TransactionFilter {
+    include_programs: HashSet<Pubkey>,
+    exclude_programs: HashSet<Pubkey>,
+    include_accounts: HashSet<Pubkey>,
+    exclude_accounts: HashSet<Pubkey>,
+    exclude_votes: bool,
+}
+
+impl TransactionFilter {
+    pub fn new(config: &TransactionFilterConfig) -> Result<Self> {
+        Ok(Self {
+            include_programs: parse_pubkeys(
+                "transaction_filter.include_programs",
+                &config.include_programs,
+            )?,
+            exclude_programs: parse_pubkeys(
+                "transaction_filter.exclude_programs",
+                &config.exclude_programs,
+            )?,
+            include_accounts: parse_pubkeys(
+                "transaction_filter.include_accounts",
+                &config.include_accounts,
+            )?,
+            exclude_accounts: parse_pubkeys(
+                "transaction_filter.exclude_accounts",
+                &config.exclude_accounts,
+            )?,
+            exclude_votes: config.exclude_votes,
+        })
+    }
+
+    /// Returns true if the transaction is archived.
+    ///
+    /// A transaction is archived if it is not a vote transaction when votes are excluded,
+    /// invokes or references none of the excluded programs and accounts and, when include
+    /// lists are set, invokes one of the included programs or references one of the
+    /// included accounts. Programs invoked through inner instructions and accounts loaded
+    /// from address lookup tables count as well.
+    pub fn wants_transaction(
+        &self,
+        is_vote: bool,
+        account_keys: &AccountKeys,
+        instructions: &[CompiledInstruction],
+        inner_instructions: Option<&[InnerInstructions]>,
+    ) -> bool {
+        if is_vote && self.exclude_votes {
+            return false;
+        }
+
+        let programs: HashSet<&Pubkey> = instructions
+            .iter()
+            .chain(
+                inner_instructions
+                    .unwrap_or_default()
+                    .iter()
+                    .flat_map(|inner| inner.instructions.iter().map(|inner| &inner.instruction)),
+            )
+            .filter_map(|instruction| account_keys.get(instruction.program_id_index as usize))
+            .collect();
+
+        if programs
+            .iter()
+            .any(|program| self.exclude_programs.contains(program))
+            || account_keys
+                .iter()
+                .any(|account| self.exclude_accounts.contains(account))
+        {
+            return false;
+        }
+
+        if self.include_programs.is_empty() && self.include_accounts.is_empty() {
+            return true;
+        }
+        programs
+            .iter()
+            .any(|program| self.include_programs.contains(program))
+            || account_keys
+                .iter()
+                .any(|account| self.include_accounts.contains(account))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        serde_json::{json, Value},
+        solana_sdk::message::v0::LoadedAddresses,
+        solana_transaction_status::InnerInstruction,
+    };
+
+    fn filter(config: Value) -> TransactionFilter {
+        TransactionFilter::new(&serde_json::from_value(config).unwrap()).unwrap()
+    }
+
+    /// A transaction whose instruction invokes `program` and whose inner instruction invokes
+    /// `inner_program`, referencing `table_account` through an address lookup table.
+    struct Fixture {
+        static_keys: Vec<Pubkey>,
+        loaded_addresses: LoadedAddresses,
+        instructions: Vec<CompiledInstruction>,
+        inner_instructions: Vec<InnerInstructions>,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let keys: Vec<_> = (0..5).map(|_| Pubkey::new_unique()).collect();
+            Self {
+                static_keys: keys[..4].to_vec(),
+                loaded_addresses: LoadedAddresses {
+                    writable: vec![keys[4]],
+                    readonly: vec![],
+                },
+                instructions: vec![CompiledInstruction::new_from_raw_parts(
+                    2,
+                    vec![],
+                    vec![0, 1],
+                )],
+                inner_instructions: vec![InnerInstructions {
+                    index: 0,
+                    instructions: vec![InnerInstruction {
+                        instruction: CompiledInstruction::new_from_raw_parts(3, vec![], vec![4]),
+                        stack_height: Some(2),
+                    }],
+                }],
+            }
+        }
+
+        fn program(&self) -> String {
+            self.static_keys[2].to_string()
+        }
+
+        fn inner_program(&self) -> String {
+            self.static_keys[3].to_string()
+        }
+
+        fn signer(&self) -> String {
+            self.static_keys[0].to_string()
+        }
+
+        fn table_account(&self) -> String {
+            self.loaded_addresses.writable[0].to_string()
+        }
+
+        fn wanted_by(&self, filter: &TransactionFilter, is_vote: bool) -> bool {
+            let account_keys = AccountKeys::new(&self.static_keys, Some(&self.loaded_addresses));
+            filter.wants_transaction(
+                is_vote,
+                &account_keys,
+                &self.instructions,
+                Some(&self.inner_instructions),
+            )
+        }
+
+        /// Whether the filter wants the transaction when its inner instructions and lookup
+        /// tables are unknown.
+        fn wanted_by_outer_only(&self, filter: &TransactionFilter) -> bool {
+            let account_keys = AccountKeys::new(&self.static_keys, None);
+            filter.wants_transaction(false, &account_keys, &self.instructions, None)
+        }
+    }
+
+    #[test]
+    fn test_votes_are_excluded_on_request() {
+        let fixture = Fixture::new();
+        assert!(fixture.wanted_by(&filter(json!({})), true));
+        let filter = filter(json!({"exclude_votes": true}));
+        assert!(!fixture.wanted_by(&filter, true));
+        assert!(fixture.wanted_by(&filter, false));
+    }
+
+    #[test]
+    fn test_include_lists() {
+        let fixture = Fixture::new();
+        let unknown = Pubkey::new_unique().to_string();
+        assert!(fixture.wanted_by(
+            &filter(json!({"include_programs": [fixture.program()]})),
+            false
+        ));
+        assert!(!fixture.wanted_by(&filter(json!({"include_programs": [unknown]})), false));
+        // A transaction matching either include list is archived.
+        let filter_accounts =
+            |account| filter(json!({"include_programs": [unknown], "include_accounts": [account]}));
+        assert!(!fixture.wanted_by(&filter_accounts(unknown.clone()), false));
+        assert!(fixture.wanted_by(&filter_accounts(fixture.signer()), false));
+
+        let filter = filter(json!({"include_programs": [fixture.inner_program()]}));
+        assert!(fixture.wanted_by(&filter, false));
+        assert!(!fixture.wanted_by_outer_only(&filter));
+    }
+
+    #[test]
+    fn test_exclude_lists() {
+        let fixture = Fixture::new();
+        assert!(!fixture.wanted_by(
+            &filter(json!({"exclude_programs": [fixture.program()]})),
+            false
+        ));
+        assert!(!fixture.wanted_by(
+            &filter(json!({"exclude_accounts": [fixture.signer()]})),
+            false
+        ));
+
+        let filter_inner = filter(json!({"exclude_programs": [fixture.inner_program()]}));
+        assert!(!fixture.wanted_by(&filter_inner, false));
+        assert!(fixture.wanted_by_outer_only(&filter_inner));
+
+        // Exclusions win over inclusions.
+        let filter = filter(json!({
+            "include_programs": [fixture.program()],
+            "exclude_accounts": [fixture.table_account()],
+        }));
+        assert!(!fixture.wanted_by(&filter, false));
+        assert!(fixture.wanted_by_outer_only(&filter));
+    }
+
+    #[test]
+    fn test_lookup_table_accounts() {
+        let fixture = Fixture::new();
+        let filter = filter(json!({"include_accounts": [fixture.table_account()]}));
+        assert!(fixture.wanted_by(&filter, false));
+        assert!(!fixture.wanted_by_outer_only(&filter));
+    }
+}
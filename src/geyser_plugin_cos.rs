@@ -1,32 +1,76 @@
 /// Main entry for the Tencent COS plugin
 use {
     crate::{
-        cos_types::{BlockInfoEvent, EntryEvent, TransactionEvent},
+        accounts::AccountsFilter,
+        admin::{AdminHandler, AdminServer},
+        cos_types::{AccountEvent, BlockInfoEvent, CosAccountInfo, EntryEvent, TransactionEvent},
         datastore::Datastore,
         errors::GeyserPluginCosError,
+        filters::TransactionFilter,
         geyser_plugin_cos_config::GeyserPluginCosConfig,
+        lock::WorkspaceLock,
+        metrics::{count_error, metrics, MetricsServer},
+        snapshot::SnapshotWriter,
+        status::{StatusSource, StatusWriter},
         storage::{Storage, StorageManager},
+        trace,
+        watchdog::{EventKind, Watchdog, WatchdogMonitor},
     },
-    log, serde_json,
+    log,
     solana_geyser_plugin_interface::geyser_plugin_interface::{
-        GeyserPlugin, GeyserPluginError, ReplicaBlockInfoVersions, ReplicaEntryInfoVersions,
-        ReplicaTransactionInfoVersions, Result, SlotStatus,
+        GeyserPlugin, GeyserPluginError, ReplicaAccountInfoVersions, ReplicaBlockInfoVersions,
+        ReplicaEntryInfoVersions, ReplicaTransactionInfoVersions, Result, SlotStatus,
     },
     solana_sdk::clock::Slot,
     solana_transaction_status::{EntrySummary, VersionedTransactionWithStatusMeta},
     std::{
-        fs::File,
-        io::Read,
-        sync::{Arc, Mutex},
+        path::Path,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
     },
 };
 
+/// State handed over to the next load when the validator reloads the plugin, as long as the
+/// library stays loaded in between.
+#[derive(Clone)]
+struct RetainedState {
+    config: GeyserPluginCosConfig,
+    datastore: Arc<Mutex<Datastore>>,
+    storage: Arc<StorageManager>,
+    paused: Arc<AtomicBool>,
+}
+
+static RETAINED_STATE: Mutex<Option<RetainedState>> = Mutex::new(None);
+
 #[derive(Default)]
 pub struct GeyserPluginCos {
+    /// The configuration loaded, or `None` before the plugin is loaded.
+    config: Option<GeyserPluginCosConfig>,
     /// In memory storage for finalized slots
     datastore: Arc<Mutex<Datastore>>,
     /// On disk storage for finalized slots.
-    storage: StorageManager,
+    storage: Arc<StorageManager>,
+    /// Rooted slots are kept in cache instead of being saved while set.
+    paused: Arc<AtomicBool>,
+    /// Accounts whose updates are archived, or `None` if account updates are not archived.
+    accounts_filter: Option<AccountsFilter>,
+    /// Transactions that are archived, or `None` if every transaction is archived.
+    transaction_filter: Option<TransactionFilter>,
+    /// Snapshot of the accounts notified during startup, or `None` if it is not written.
+    snapshot: Option<SnapshotWriter>,
+    /// Metrics HTTP server, or `None` if metrics are not served.
+    metrics_server: Option<MetricsServer>,
+    /// Admin server, or `None` if the admin API is disabled.
+    admin_server: Option<AdminServer>,
+    /// Writer of `status.json`, or `None` if the status file is not written.
+    status_writer: Option<StatusWriter>,
+    /// Time of the last notification of each kind.
+    watchdog: Arc<Watchdog>,
+    /// Checks the watchdog, or `None` if no notification is monitored.
+    watchdog_monitor: Option<WatchdogMonitor>,
 }
 
 impl std::fmt::Debug for GeyserPluginCos {
@@ -54,31 +98,113 @@ impl GeyserPlugin for GeyserPluginCos {
     ///    "slot_range": 1000
     ///    "commit_slot_delay": 100
     /// }
-    fn on_load(&mut self, config_file: &str, _is_reload: bool) -> Result<()> {
+    fn on_load(&mut self, config_file: &str, is_reload: bool) -> Result<()> {
         solana_logger::setup_with_default("info");
 
         let plugin_name = self.name();
         log::info!("COS: Loading plugin {plugin_name} from config_file {config_file}");
 
-        let mut file = File::open(config_file)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-        let config: GeyserPluginCosConfig = serde_json::from_str(&contents).map_err(|err| {
-            log::error!("COS: The config file is not in the JSON format expected: {err:?}");
-            GeyserPluginError::ConfigFileReadError {
-                msg: format!("COS: The config file is not in the JSON format expected: {err:?}"),
+        let mut config = GeyserPluginCosConfig::load(config_file)?;
+        // The previous state is only taken once the new configuration is known to be valid,
+        // so that a reload with a bad configuration keeps the cached slots for the next one.
+        let previous_config = RETAINED_STATE
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|_| is_reload)
+            .map(|previous| previous.config.clone());
+        match &previous_config {
+            Some(previous_config) => {
+                for field in config.keep_unsafe_fields(previous_config) {
+                    log::error!(
+                        "COS: {field} cannot change on reload, keeping the previous value; \
+                         restart the validator to apply it"
+                    );
+                }
+                config.validate()?;
             }
-        })?;
+            None if is_reload => {
+                log::warn!(
+                    "COS: The state of the previous load is not available, cached slots are lost"
+                );
+            }
+            None => {}
+        }
+        // Lock the workspace before anything touches it, e.g. the snapshot writer removing
+        // the interrupted snapshots, unless the storage of the previous load holding the lock
+        // is taken over.
+        let workspace_lock = match previous_config {
+            Some(_) => None,
+            None => Some(WorkspaceLock::acquire(Path::new(&config.workspace))?),
+        };
+        config.check_workspace()?;
+        if let Some(log_level) = &config.log_level {
+            solana_logger::setup_with(log_level);
+        }
+        trace::configure(config.tracing.as_ref())?;
 
-        self.datastore = Arc::new(Mutex::new(Datastore::new(&config)));
-        self.storage = StorageManager::new(&config)?;
+        self.accounts_filter = config
+            .accounts
+            .as_ref()
+            .map(AccountsFilter::new)
+            .transpose()?;
+        self.transaction_filter = config
+            .transaction_filter
+            .as_ref()
+            .map(TransactionFilter::new)
+            .transpose()?;
+        // The accounts are only notified at startup, not when the plugin is reloaded.
+        self.snapshot = config
+            .startup_snapshot
+            .as_ref()
+            .filter(|_| !is_reload)
+            .map(|snapshot_config| SnapshotWriter::new(&config, snapshot_config.chunk_size))
+            .transpose()?;
 
+        // Stop the server of a previous load before binding the address again.
+        self.metrics_server = None;
+        self.metrics_server = config
+            .metrics_address
+            .map(MetricsServer::start)
+            .transpose()?;
+
+        self.watchdog_monitor = None;
+        self.watchdog = Arc::new(Watchdog::new(&config.watchdog));
+        if self.watchdog.is_enabled() {
+            self.watchdog_monitor = Some(WatchdogMonitor::start(self.watchdog.clone())?);
+        }
+
+        let previous = RETAINED_STATE
+            .lock()
+            .unwrap()
+            .take()
+            .filter(|_| previous_config.is_some());
+        if let Err(err) = self.start_storage(&config, previous.clone(), workspace_lock) {
+            // Hand the cached slots over to the next load instead.
+            *RETAINED_STATE.lock().unwrap() = previous;
+            return Err(err);
+        }
+
+        self.config = Some(config);
         Ok(())
     }
 
     fn on_unload(&mut self) {
         let plugin_name = self.name();
         log::info!("COS: Unloading plugin: {plugin_name}");
+        self.metrics_server = None;
+        self.admin_server = None;
+        self.status_writer = None;
+        self.watchdog_monitor = None;
+        let _ = trace::configure(None);
+        if let Some(config) = self.config.take() {
+            *RETAINED_STATE.lock().unwrap() = Some(RetainedState {
+                config,
+                datastore: self.datastore.clone(),
+                storage: self.storage.clone(),
+                paused: self.paused.clone(),
+            });
+        }
     }
 
     fn update_slot_status(
@@ -88,7 +214,57 @@ impl GeyserPlugin for GeyserPluginCos {
         status: SlotStatus,
     ) -> Result<()> {
         log::info!("COS: Slot {slot} status: {status:?}");
-        self.on_slot_status(slot, status)
+        self.watchdog.record(EventKind::SlotStatus);
+        if status == SlotStatus::Rooted {
+            self.watchdog.record(EventKind::Rooted);
+        }
+        count_error(self.on_slot_status(slot, status))
+    }
+
+    fn update_account(
+        &self,
+        account: ReplicaAccountInfoVersions,
+        slot: Slot,
+        is_startup: bool,
+    ) -> Result<()> {
+        // Startup notifications go to the snapshot, the other ones to the accounts table.
+        if is_startup && self.snapshot.is_none() || !is_startup && self.accounts_filter.is_none() {
+            return Ok(());
+        }
+        count_error(match account {
+            ReplicaAccountInfoVersions::V0_0_1(_) | ReplicaAccountInfoVersions::V0_0_2(_) => {
+                Err(GeyserPluginError::Custom(Box::new(
+                    GeyserPluginCosError::ReplicaAccountV001NotSupported,
+                )))
+            }
+            ReplicaAccountInfoVersions::V0_0_3(account_info) => {
+                match CosAccountInfo::try_from(account_info) {
+                    Ok(account) => {
+                        log::debug!(
+                            "COS: Slot {slot} account = {} write_version = {} startup = \
+                             {is_startup}",
+                            account.pubkey,
+                            account.write_version
+                        );
+                        let account_event = AccountEvent { slot, account };
+                        if is_startup {
+                            self.on_startup_account(account_event)
+                        } else {
+                            self.on_account(account_event)
+                        }
+                    }
+                    Err(err) => Err(GeyserPluginError::Custom(Box::new(err))),
+                }
+            }
+        })
+    }
+
+    fn notify_end_of_startup(&self) -> Result<()> {
+        self.watchdog.start();
+        if let Some(snapshot) = &self.snapshot {
+            count_error(snapshot.finish().map_err(GeyserPluginError::from))?;
+        }
+        Ok(())
     }
 
     fn notify_transaction(
@@ -96,13 +272,36 @@ impl GeyserPlugin for GeyserPluginCos {
         transaction_info: ReplicaTransactionInfoVersions,
         slot: u64,
     ) -> Result<()> {
-        match transaction_info {
+        self.watchdog.record(EventKind::Transaction);
+        count_error(match transaction_info {
             ReplicaTransactionInfoVersions::V0_0_1(_) => Err(GeyserPluginError::Custom(Box::new(
                 GeyserPluginCosError::ReplicaTransactionV001NotSupported,
             ))),
             ReplicaTransactionInfoVersions::V0_0_2(transaction_info) => {
+                if let Some(transaction_filter) = &self.transaction_filter {
+                    let message = transaction_info.transaction.message();
+                    if !transaction_filter.wants_transaction(
+                        transaction_info.is_vote,
+                        &message.account_keys(),
+                        message.instructions(),
+                        transaction_info
+                            .transaction_status_meta
+                            .inner_instructions
+                            .as_deref(),
+                    ) {
+                        log::debug!(
+                            "COS: Slot {slot} index = {} transaction = {} filtered",
+                            transaction_info.index,
+                            transaction_info.signature
+                        );
+                        return count_error(
+                            self.on_filtered_transaction(slot, transaction_info.index),
+                        );
+                    }
+                }
                 let transaction = TransactionEvent {
                     slot,
+                    is_vote: transaction_info.is_vote,
                     transaction: transaction_info.into(),
                 };
                 log::debug!(
@@ -112,11 +311,12 @@ impl GeyserPlugin for GeyserPluginCos {
                 );
                 self.on_transaction(transaction)
             }
-        }
+        })
     }
 
     fn notify_block_metadata(&self, block_info: ReplicaBlockInfoVersions) -> Result<()> {
-        match block_info {
+        self.watchdog.record(EventKind::BlockMetadata);
+        count_error(match block_info {
             ReplicaBlockInfoVersions::V0_0_1(_) => Err(GeyserPluginError::Custom(Box::new(
                 GeyserPluginCosError::ReplicaBlockV001NotSupported,
             ))),
@@ -137,11 +337,12 @@ impl GeyserPlugin for GeyserPluginCos {
                 );
                 self.on_block_info(block_info_event)
             }
-        }
+        })
     }
 
     fn notify_entry(&self, entry: ReplicaEntryInfoVersions<'_>) -> Result<()> {
-        match entry {
+        self.watchdog.record(EventKind::Entry);
+        count_error(match entry {
             ReplicaEntryInfoVersions::V0_0_1(_) => Err(GeyserPluginError::Custom(Box::new(
                 GeyserPluginCosError::ReplicaEntryV001NotSupported,
             ))),
@@ -155,7 +356,11 @@ impl GeyserPlugin for GeyserPluginCos {
                 );
                 self.on_entry(entry_event)
             }
-        }
+        })
+    }
+
+    fn account_data_notifications_enabled(&self) -> bool {
+        self.accounts_filter.is_some() || self.snapshot.is_some()
     }
 
     fn transaction_notifications_enabled(&self) -> bool {
@@ -172,13 +377,122 @@ impl GeyserPluginCos {
         Self::default()
     }
 
+    /// Takes over the cache and storage of the `previous` load, or opens the workspace with
+    /// `workspace_lock`, and starts the services reading them.
+    fn start_storage(
+        &mut self,
+        config: &GeyserPluginCosConfig,
+        previous: Option<RetainedState>,
+        workspace_lock: Option<WorkspaceLock>,
+    ) -> Result<()> {
+        // Stop reading the previous storage before the staging folder is cleaned up.
+        self.status_writer = None;
+        self.admin_server = None;
+        let (datastore, storage, paused) = match previous {
+            Some(previous) => {
+                let storage = Arc::new(previous.storage.reload(config)?);
+                log::info!(
+                    "COS: Reloaded the configuration, keeping {} cached slots",
+                    previous.datastore.lock().unwrap().cache_size()
+                );
+                (previous.datastore, storage, previous.paused)
+            }
+            None => {
+                let storage = match workspace_lock {
+                    Some(workspace_lock) => StorageManager::with_lock(config, workspace_lock)?,
+                    None => StorageManager::new(config)?,
+                };
+                (
+                    Arc::new(Mutex::new(Datastore::new(config))),
+                    Arc::new(storage),
+                    Arc::new(AtomicBool::new(false)),
+                )
+            }
+        };
+
+        let admin_server = config
+            .admin_address
+            .map(|address| {
+                AdminServer::start(
+                    address,
+                    AdminHandler::new(datastore.clone(), storage.clone(), paused.clone()),
+                )
+            })
+            .transpose()?;
+
+        let status_writer = if config.status_interval_secs > 0 {
+            Some(StatusWriter::start(
+                &config.workspace,
+                Duration::from_secs(config.status_interval_secs),
+                StatusSource {
+                    datastore: datastore.clone(),
+                    storage: storage.clone(),
+                    paused: paused.clone(),
+                    watchdog: self.watchdog.clone(),
+                    config_version: config.version(),
+                },
+            )?)
+        } else {
+            None
+        };
+
+        self.datastore = datastore;
+        self.storage = storage;
+        self.paused = paused;
+        self.admin_server = admin_server;
+        self.status_writer = status_writer;
+        Ok(())
+    }
+
+    fn on_account(&self, account_event: AccountEvent) -> Result<()> {
+        // The snapshot holds every account, the filter only applies to the accounts table.
+        if let Some(accounts_filter) = &self.accounts_filter {
+            let account = &account_event.account;
+            if !accounts_filter.wants_account(&account.pubkey, &account.owner) {
+                return Ok(());
+            }
+        }
+        let mut datastore = self.datastore.lock().unwrap();
+        if !datastore.in_window(account_event.slot) {
+            return Ok(());
+        }
+        datastore.check_first_slot(account_event.slot)?;
+
+        let block_with_entries = datastore.get_mut_entry(account_event.slot);
+        tracing::debug!(
+            parent: &block_with_entries.span,
+            pubkey = %account_event.account.pubkey,
+            write_version = account_event.account.write_version,
+            "Account update"
+        );
+        block_with_entries.accounts.push(account_event.account);
+
+        Ok(())
+    }
+
+    fn on_startup_account(&self, account_event: AccountEvent) -> Result<()> {
+        if let Some(snapshot) = &self.snapshot {
+            snapshot.add(account_event.slot, account_event.account.into())?;
+        }
+        Ok(())
+    }
+
     fn on_transaction(&self, tx_event: TransactionEvent) -> Result<()> {
         let mut datastore = self.datastore.lock().unwrap();
+        if !datastore.in_window(tx_event.slot) {
+            return Ok(());
+        }
         datastore.check_first_slot(tx_event.slot)?;
 
         let block_with_entries = datastore.get_mut_entry(tx_event.slot);
 
         let index = tx_event.transaction.meta.index;
+        tracing::debug!(
+            parent: &block_with_entries.span,
+            index,
+            is_vote = tx_event.is_vote,
+            "Transaction"
+        );
         if index >= block_with_entries.block.transactions.len() {
             block_with_entries.block.transactions.resize(
                 tx_event.transaction.meta.index + 1,
@@ -189,17 +503,46 @@ impl GeyserPluginCos {
             );
         }
         block_with_entries.block.transactions[index] = tx_event.transaction.into();
+        if tx_event.is_vote {
+            block_with_entries.vote_transaction_indexes.insert(index);
+        }
+
+        Ok(())
+    }
+
+    fn on_filtered_transaction(&self, slot: Slot, index: usize) -> Result<()> {
+        let mut datastore = self.datastore.lock().unwrap();
+        if !datastore.in_window(slot) {
+            return Ok(());
+        }
+        datastore.check_first_slot(slot)?;
+
+        let block_with_entries = datastore.get_mut_entry(slot);
+        tracing::debug!(parent: &block_with_entries.span, index, "Filtered transaction");
+        block_with_entries
+            .filtered_transaction_indexes
+            .insert(index);
 
         Ok(())
     }
 
     fn on_block_info(&self, block_info_event: BlockInfoEvent) -> Result<()> {
         let mut datastore = self.datastore.lock().unwrap();
+        if !datastore.in_window(block_info_event.slot) {
+            return Ok(());
+        }
         datastore.check_first_slot(block_info_event.slot)?;
 
         let block_with_entries = datastore.get_mut_entry(block_info_event.slot);
 
         log::debug!("COS: BlockInfoEvent {block_info_event:?}");
+        tracing::info!(
+            parent: &block_with_entries.span,
+            blockhash = block_info_event.blockhash,
+            executed_transaction_count = block_info_event.executed_transaction_count,
+            entry_count = block_info_event.entry_count,
+            "Block metadata"
+        );
 
         block_with_entries.block.previous_blockhash = block_info_event.parent_blockhash;
         block_with_entries.block.blockhash = block_info_event.blockhash;
@@ -215,11 +558,15 @@ impl GeyserPluginCos {
 
     fn on_entry(&self, entry_event: EntryEvent) -> Result<()> {
         let mut datastore = self.datastore.lock().unwrap();
+        if !datastore.in_window(entry_event.slot) {
+            return Ok(());
+        }
         datastore.check_first_slot(entry_event.slot)?;
 
         let block_with_entries = datastore.get_mut_entry(entry_event.slot);
 
         let index = entry_event.index;
+        tracing::debug!(parent: &block_with_entries.span, index, "Entry");
         if index >= block_with_entries.entries.len() {
             block_with_entries
                 .entries
@@ -238,19 +585,32 @@ impl GeyserPluginCos {
     fn on_slot_status(&self, slot: Slot, status: SlotStatus) -> Result<()> {
         {
             let mut datastore = self.datastore.lock().unwrap();
-            datastore.check_first_slot(slot)?;
+            // Roots past the end of the capture window still trigger its final save.
+            if datastore.in_window(slot) {
+                datastore.check_first_slot(slot)?;
 
-            let block_with_entries = datastore.get_mut_entry(slot);
+                let block_with_entries = datastore.get_mut_entry(slot);
+                tracing::info!(parent: &block_with_entries.span, ?status, "Slot status");
 
-            block_with_entries.slot_status = status;
+                block_with_entries.slot_status = status;
+                block_with_entries.timings.record_status(status);
+            }
         }
         match status {
-            SlotStatus::Rooted => self.on_slot_rooted(slot),
+            SlotStatus::Rooted => {
+                self.datastore.lock().unwrap().set_rooted(slot);
+                metrics().record_rooted(slot);
+                self.on_slot_rooted(slot)
+            }
             _ => Ok(()),
         }
     }
 
     fn on_slot_rooted(&self, slot: Slot) -> Result<()> {
+        if self.paused.load(Ordering::Relaxed) {
+            log::debug!("COS: Writes are paused, keeping slot {slot} in cache");
+            return Ok(());
+        }
         // NOTE: We have no guaranteed order of events for current slot.
         // (e.g. it might be that we still need to process some transactions for the current slot
         // when we receive slot status rooted).
@@ -261,27 +621,87 @@ impl GeyserPluginCos {
         let first_slot = if slot >= 100 { slot - 100 } else { 0 };
         let last_slot = if slot >= 10 { slot - 10 } else { 0 };
 
+        let (end_slot, complete) = {
+            let datastore = self.datastore.lock().unwrap();
+            (datastore.end_slot(), datastore.is_complete())
+        };
+        if complete {
+            return Ok(());
+        }
         if last_slot > 0 {
-            for prev_slot in first_slot..=last_slot {
-                let block_with_entries;
-                {
-                    // Unlock mutex as soon as possible
-                    let mut datastore = self.datastore.lock().unwrap();
-                    block_with_entries = datastore.remove_entry(prev_slot);
-                }
-                if let Some(block_with_entries) = block_with_entries {
-                    if block_with_entries.slot_status != SlotStatus::Rooted {
-                        log::debug!("COS: Slot {prev_slot} is not rooted, discarding");
-                    } else {
-                        log::debug!("COS: Saving slot {prev_slot} to storage");
+            save_slots(
+                &self.datastore,
+                &self.storage,
+                first_slot..=last_slot.min(end_slot.unwrap_or(Slot::MAX)),
+            )?;
+        }
+        if let Some(end_slot) = end_slot.filter(|end_slot| last_slot >= *end_slot) {
+            self.complete_window(end_slot)?;
+        }
+        Ok(())
+    }
 
-                        self.storage.save(prev_slot, &block_with_entries)?;
-                    }
-                }
+    /// Saves or discards the slots left in cache, commits the last range and stops caching
+    /// slots, once the end of the capture window is rooted.
+    fn complete_window(&self, end_slot: Slot) -> Result<()> {
+        let slots = {
+            let datastore = self.datastore.lock().unwrap();
+            let mut slots: Vec<Slot> = datastore.entries().map(|(slot, _)| *slot).collect();
+            slots.sort_unstable();
+            slots
+        };
+        let summary = save_slots(&self.datastore, &self.storage, slots)?;
+        self.storage.commit_all()?;
+        self.datastore.lock().unwrap().set_complete();
+        log::info!(
+            "COS: Captured every slot up to end slot {end_slot}, going idle ({} saved, {} \
+             discarded on completion)",
+            summary.saved,
+            summary.discarded
+        );
+        Ok(())
+    }
+}
+
+/// Number of slots saved and discarded by `save_slots`.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct SaveSummary {
+    pub saved: u64,
+    pub discarded: u64,
+}
+
+/// Removes `slots` from the cache, saving the rooted ones to storage and discarding the others.
+pub(crate) fn save_slots(
+    datastore: &Mutex<Datastore>,
+    storage: &StorageManager,
+    slots: impl IntoIterator<Item = Slot>,
+) -> Result<SaveSummary> {
+    let mut summary = SaveSummary::default();
+    for slot in slots {
+        let block_with_entries;
+        {
+            // Unlock mutex as soon as possible
+            let mut datastore = datastore.lock().unwrap();
+            block_with_entries = datastore.remove_entry(slot);
+        }
+        if let Some(block_with_entries) = block_with_entries {
+            let _entered = block_with_entries.span.enter();
+            if block_with_entries.slot_status != SlotStatus::Rooted {
+                log::debug!("COS: Slot {slot} is not rooted, discarding");
+                tracing::info!("Discarded");
+                metrics().slots_discarded.inc();
+                summary.discarded += 1;
+            } else {
+                log::debug!("COS: Saving slot {slot} to storage");
+
+                let _timer = metrics().save_seconds.start_timer();
+                storage.save(slot, &block_with_entries)?;
+                metrics().slots_saved.inc();
+                summary.saved += 1;
             }
         }
-        Ok(())
     }
+    Ok(summary)
 }
 
 #[no_mangle]
@@ -294,3 +714,363 @@ pub unsafe extern "C" fn _create_plugin() -> *mut dyn GeyserPlugin {
     let plugin: Box<dyn GeyserPlugin> = Box::new(plugin);
     Box::into_raw(plugin)
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            cos_types::{CosTransactionStatusMeta, CosVersionedTransactionWithStatusMeta},
+            reader::{RangeLocation, WorkspaceReader},
+            templates::Naming,
+        },
+        serde_json::{json, Value},
+        solana_sdk::{hash::Hash, pubkey::Pubkey, system_instruction, transaction::Transaction},
+        solana_transaction_status::{Reward, RewardType},
+        std::{fs, path::PathBuf},
+    };
+
+    /// The tests loading the plugin share `RETAINED_STATE`, so they run one at a time.
+    static PLUGIN_TESTS: Mutex<()> = Mutex::new(());
+
+    /// An empty workspace named after the test, and the path of its config file, with ranges
+    /// of 10 slots and `settings` on top.
+    fn setup(name: &str, settings: Value) -> (PathBuf, String) {
+        let workspace =
+            std::env::temp_dir().join(format!("cos-plugin-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&workspace);
+        let mut config = json!({
+            "workspace": workspace,
+            "genesis_hash": Hash::default().to_string(),
+            "slot_range": 10,
+            "commit_slot_delay": 5,
+            "status_interval_secs": 0,
+        });
+        config
+            .as_object_mut()
+            .unwrap()
+            .extend(settings.as_object().unwrap().clone());
+        let config_file = workspace.with_extension("json");
+        fs::write(&config_file, config.to_string()).unwrap();
+        (workspace, config_file.to_str().unwrap().to_string())
+    }
+
+    /// A transfer from `payer` notified at `index` in `slot`.
+    fn transaction_event(slot: Slot, index: usize, payer: &Pubkey) -> TransactionEvent {
+        let instruction = system_instruction::transfer(payer, &Pubkey::new_unique(), 1);
+        TransactionEvent {
+            slot,
+            is_vote: false,
+            transaction: CosVersionedTransactionWithStatusMeta {
+                transaction: Transaction::new_with_payer(&[instruction], Some(payer)).into(),
+                meta: CosTransactionStatusMeta {
+                    status: None,
+                    loaded_addresses: Default::default(),
+                    index,
+                },
+            },
+        }
+    }
+
+    fn block_info_event(slot: Slot, rewards: Vec<Reward>, transactions: u64) -> BlockInfoEvent {
+        BlockInfoEvent {
+            parent_slot: slot - 1,
+            parent_blockhash: Hash::new_from_array([slot as u8 - 1; 32]).to_string(),
+            slot,
+            blockhash: Hash::new_from_array([slot as u8; 32]).to_string(),
+            rewards,
+            block_time: Some(1_700_000_000),
+            block_height: Some(slot),
+            executed_transaction_count: transactions,
+            entry_count: 0,
+        }
+    }
+
+    /// Roots `slot` in the cache of `plugin` and saves it to staging.
+    fn save_slot(plugin: &GeyserPluginCos, slot: Slot) {
+        plugin
+            .datastore
+            .lock()
+            .unwrap()
+            .get_mut_entry(slot)
+            .slot_status = SlotStatus::Rooted;
+        let summary = save_slots(&plugin.datastore, &plugin.storage, [slot]).unwrap();
+        assert_eq!(summary.saved, 1);
+    }
+
+    /// The files of the staged `slot` of `workspace`, relative to the slot folder, with their
+    /// content.
+    fn staged_slot_files(workspace: &Path, slot: Slot) -> Vec<(PathBuf, Vec<u8>)> {
+        let naming = Naming::default();
+        let staging_path = workspace.join("staging");
+        let range = &naming.list_ranges(&staging_path).unwrap()[0];
+        let slot_path = staging_path
+            .join(&range.name)
+            .join(naming.slot_folder(slot));
+        let mut files = vec![];
+        let mut folders = vec![slot_path.clone()];
+        while let Some(folder) = folders.pop() {
+            for entry in fs::read_dir(folder).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    folders.push(path);
+                } else {
+                    let content = fs::read(&path).unwrap();
+                    files.push((path.strip_prefix(&slot_path).unwrap().to_owned(), content));
+                }
+            }
+        }
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_canonical_cells_do_not_depend_on_the_notification_order() {
+        let _guard = PLUGIN_TESTS.lock().unwrap_or_else(|err| err.into_inner());
+        let slot = 20;
+        let payer = Pubkey::new_unique();
+        let transactions: Vec<_> = (0..3)
+            .map(|index| transaction_event(slot, index, &payer))
+            .collect();
+        let rewards: Vec<_> = (0..3)
+            .map(|lamports| Reward {
+                pubkey: Pubkey::new_unique().to_string(),
+                lamports,
+                post_balance: 1_000,
+                reward_type: Some(RewardType::Fee),
+                commission: None,
+            })
+            .collect();
+
+        let mut saved = vec![];
+        for (name, order) in [("canonical-a", [0, 1, 2]), ("canonical-b", [2, 0, 1])] {
+            let (workspace, config_file) = setup(name, json!({"canonical": true}));
+            let mut plugin = GeyserPluginCos::new();
+            plugin.on_load(&config_file, false).unwrap();
+            for index in order {
+                plugin.on_transaction(transactions[index].clone()).unwrap();
+            }
+            let rewards = order.iter().map(|index| rewards[*index].clone()).collect();
+            plugin
+                .on_block_info(block_info_event(slot, rewards, 3))
+                .unwrap();
+            save_slot(&plugin, slot);
+            saved.push(staged_slot_files(&workspace, slot));
+
+            drop(plugin);
+            fs::remove_dir_all(workspace).unwrap();
+            fs::remove_file(config_file).unwrap();
+        }
+        let names: Vec<_> = saved[0].iter().map(|(name, _)| name.clone()).collect();
+        assert_eq!(
+            names,
+            vec![
+                PathBuf::from("blocks/0000000000000014.proto"),
+                PathBuf::from("content_hashes.json"),
+            ]
+        );
+        assert_eq!(saved[0], saved[1]);
+    }
+
+    #[test]
+    fn test_locked_workspace_is_left_untouched() {
+        let _guard = PLUGIN_TESTS.lock().unwrap_or_else(|err| err.into_inner());
+        let (workspace, config_file) =
+            setup("locked", json!({"startup_snapshot": {"chunk_size": 10}}));
+        let mut plugin = GeyserPluginCos::new();
+        plugin.on_load(&config_file, false).unwrap();
+        // The snapshot the first plugin is writing.
+        let partial_path = workspace.join("snapshots/snapshot_0000000000000064.partial");
+        fs::create_dir_all(&partial_path).unwrap();
+
+        let err = GeyserPluginCos::new()
+            .on_load(&config_file, false)
+            .unwrap_err();
+        assert!(err.to_string().contains("is locked by"), "{err}");
+        assert!(partial_path.is_dir());
+
+        drop(plugin);
+        fs::remove_dir_all(workspace).unwrap();
+        fs::remove_file(config_file).unwrap();
+    }
+
+    #[test]
+    fn test_reload_keeps_the_cache_and_the_lock() {
+        let _guard = PLUGIN_TESTS.lock().unwrap_or_else(|err| err.into_inner());
+        let (workspace, config_file) = setup("reload", json!({}));
+        let mut plugin = GeyserPluginCos::new();
+        plugin.on_load(&config_file, false).unwrap();
+        plugin
+            .on_block_info(block_info_event(20, vec![], 0))
+            .unwrap();
+        save_slot(&plugin, 20);
+        plugin
+            .on_block_info(block_info_event(21, vec![], 0))
+            .unwrap();
+        let datastore = plugin.datastore.clone();
+
+        // The validator unloads the plugin and loads it again, keeping the library loaded.
+        plugin.on_unload();
+        drop(plugin);
+        let mut plugin = GeyserPluginCos::new();
+        plugin.on_load(&config_file, true).unwrap();
+        assert!(Arc::ptr_eq(&plugin.datastore, &datastore));
+        assert_eq!(plugin.datastore.lock().unwrap().cache_size(), 1);
+        // The staging folder is not wiped, and the lock of the first load is kept.
+        assert_eq!(staged_slot_files(&workspace, 20).len(), 1);
+        assert!(WorkspaceLock::acquire(&workspace).is_err());
+
+        drop(plugin);
+        WorkspaceLock::acquire(&workspace).unwrap();
+        fs::remove_dir_all(workspace).unwrap();
+        fs::remove_file(config_file).unwrap();
+    }
+
+    #[test]
+    fn test_reload_keeps_the_workspace() {
+        let _guard = PLUGIN_TESTS.lock().unwrap_or_else(|err| err.into_inner());
+        let (workspace, config_file) = setup("reload-workspace", json!({}));
+        let mut plugin = GeyserPluginCos::new();
+        plugin.on_load(&config_file, false).unwrap();
+        plugin.on_unload();
+        drop(plugin);
+
+        let other_workspace = workspace.with_extension("other");
+        let mut config: Value = serde_json::from_slice(&fs::read(&config_file).unwrap()).unwrap();
+        config["workspace"] = json!(other_workspace);
+        fs::write(&config_file, config.to_string()).unwrap();
+        let mut plugin = GeyserPluginCos::new();
+        plugin.on_load(&config_file, true).unwrap();
+        assert_eq!(
+            plugin.config.as_ref().unwrap().workspace,
+            workspace.to_str().unwrap()
+        );
+        assert!(!other_workspace.exists());
+
+        drop(plugin);
+        fs::remove_dir_all(workspace).unwrap();
+        fs::remove_file(config_file).unwrap();
+    }
+
+    #[test]
+    fn test_capture_window() {
+        let _guard = PLUGIN_TESTS.lock().unwrap_or_else(|err| err.into_inner());
+        let (workspace, config_file) = setup("window", json!({"start_slot": 15, "end_slot": 27}));
+        let mut plugin = GeyserPluginCos::new();
+        plugin.on_load(&config_file, false).unwrap();
+        for slot in 10..=36 {
+            plugin
+                .on_block_info(block_info_event(slot, vec![], 0))
+                .unwrap();
+            plugin.on_slot_status(slot, SlotStatus::Rooted).unwrap();
+        }
+        assert!(!plugin.datastore.lock().unwrap().is_complete());
+        // The root 10 slots after the end slot completes the window.
+        plugin
+            .on_block_info(block_info_event(37, vec![], 0))
+            .unwrap();
+        plugin.on_slot_status(37, SlotStatus::Rooted).unwrap();
+        assert!(plugin.datastore.lock().unwrap().is_complete());
+        assert_eq!(plugin.datastore.lock().unwrap().cache_size(), 0);
+
+        let reader = WorkspaceReader::open(&workspace).unwrap();
+        let ranges: Vec<_> = reader
+            .ranges()
+            .unwrap()
+            .into_iter()
+            .map(|range| (range.start, range.end, range.location))
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (10, 20, RangeLocation::Storage),
+                (20, 30, RangeLocation::Storage)
+            ]
+        );
+        let slots: Vec<_> = reader.all_slots().unwrap().into_keys().collect();
+        assert_eq!(slots, (15..=27).collect::<Vec<_>>());
+
+        // Later slots are neither cached nor saved.
+        plugin
+            .on_block_info(block_info_event(48, vec![], 0))
+            .unwrap();
+        plugin.on_slot_status(48, SlotStatus::Rooted).unwrap();
+        assert_eq!(plugin.datastore.lock().unwrap().cache_size(), 0);
+        assert_eq!(reader.all_slots().unwrap().len(), 13);
+
+        drop(plugin);
+        fs::remove_dir_all(workspace).unwrap();
+        fs::remove_file(config_file).unwrap();
+    }
+
+    #[test]
+    fn test_account_updates_are_committed() {
+        let _guard = PLUGIN_TESTS.lock().unwrap_or_else(|err| err.into_inner());
+        let owner = Pubkey::new_unique();
+        let (workspace, config_file) = setup(
+            "accounts",
+            json!({"accounts": {"owners": [owner.to_string()]}}),
+        );
+        let mut plugin = GeyserPluginCos::new();
+        plugin.on_load(&config_file, false).unwrap();
+        let pubkeys = [Pubkey::new_unique(), Pubkey::new_unique()];
+        for (pubkey, owner, write_version) in [
+            (pubkeys[1], owner, 7),
+            (Pubkey::new_unique(), Pubkey::new_unique(), 6),
+            (pubkeys[0], owner, 5),
+        ] {
+            let account = CosAccountInfo {
+                pubkey,
+                owner,
+                lamports: 1,
+                executable: false,
+                rent_epoch: 0,
+                data: vec![],
+                write_version,
+                txn_signature: None,
+            };
+            plugin
+                .on_account(AccountEvent { slot: 20, account })
+                .unwrap();
+        }
+        // Slot 20 is saved once slot 30 is rooted, and its range is committed with the save
+        // of slot 35, once slot 45 is rooted.
+        for slot in 20..=45 {
+            plugin
+                .on_block_info(block_info_event(slot, vec![], 0))
+                .unwrap();
+            plugin.on_slot_status(slot, SlotStatus::Rooted).unwrap();
+        }
+
+        let slot = WorkspaceReader::open(&workspace)
+            .unwrap()
+            .all_slots()
+            .unwrap()
+            .remove(&20)
+            .unwrap();
+        assert_eq!(
+            slot.cell_path("accounts"),
+            Some(workspace.join(
+                "storage/range_0000000000000014_000000000000001e/slot_0000000000000014/\
+                 accounts/0000000000000014.proto"
+            ))
+        );
+        let accounts = WorkspaceReader::read_accounts(&slot).unwrap().unwrap();
+        let updates: Vec<_> = accounts
+            .accounts
+            .iter()
+            .map(|account| (account.pubkey.clone(), account.write_version))
+            .collect();
+        assert_eq!(
+            updates,
+            vec![
+                (pubkeys[0].to_bytes().to_vec(), 5),
+                (pubkeys[1].to_bytes().to_vec(), 7)
+            ]
+        );
+
+        drop(plugin);
+        fs::remove_dir_all(workspace).unwrap();
+        fs::remove_file(config_file).unwrap();
+    }
+}
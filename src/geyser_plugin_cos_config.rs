@@ -1,12 +1,662 @@
-use serde::{Deserialize, Serialize};
+use {
+    crate::{
+        accounts::parse_pubkeys,
+        cluster::{cluster_name, known_genesis_hash, ledger_genesis_hash},
+        compression::{CompressionMethod, CANONICAL_COMPRESSION_METHOD},
+        layout::{RangePolicy, WorkspaceLayout},
+        templates::KeyTemplates,
+    },
+    serde::{Deserialize, Serialize},
+    serde_json::{Map, Value},
+    solana_geyser_plugin_interface::geyser_plugin_interface::{GeyserPluginError, Result},
+    solana_sdk::{clock::Slot, epoch_schedule::MINIMUM_SLOTS_PER_EPOCH, hash::Hash},
+    std::{
+        fs::{self, File},
+        io::Read,
+        net::SocketAddr,
+        path::Path,
+        str::FromStr,
+    },
+    tracing_subscriber::filter::Targets,
+};
+
+/// Prefix of the environment variables overriding the settings.
+const ENV_PREFIX: &str = "COS_PLUGIN_";
 
 /// The Configuration
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[non_exhaustive]
 pub struct GeyserPluginCosConfig {
+    /// Path of the plugin library, read by the validator.
+    #[serde(default)]
+    pub libpath: Option<String>,
     /// The folder path were block information is saved.
     pub workspace: String,
     /// The maximum number of slots to include in a slot range.
     pub slot_range: u64,
     /// Commit slot delay in number of slots.
     pub commit_slot_delay: u64,
+    /// How slots are grouped into ranges. Fixed ranges of `slot_range` slots when not set.
+    #[serde(default)]
+    pub range_policy: RangePolicy,
+    /// First slot to capture. When not set, capture starts at the first complete slot range.
+    #[serde(default)]
+    pub start_slot: Option<Slot>,
+    /// Last slot to capture (inclusive). When set, the plugin commits the last range and goes
+    /// idle once the slot is rooted.
+    #[serde(default)]
+    pub end_slot: Option<Slot>,
+    /// Name of the cluster, replacing the `{cluster}` placeholder of the templates. Derived
+    /// from the genesis hash of the public clusters when not set.
+    #[serde(default)]
+    pub cluster: Option<String>,
+    /// Genesis hash of the cluster. Read from the `genesis.bin` file of `ledger_path` when not
+    /// set.
+    #[serde(default)]
+    pub genesis_hash: Option<String>,
+    /// Ledger folder of the validator, to derive the genesis hash.
+    #[serde(default)]
+    pub ledger_path: Option<String>,
+    /// Identity pubkey of the validator, replacing the `{identity}` placeholder of the
+    /// templates.
+    #[serde(default)]
+    pub identity: Option<String>,
+    /// Templates of the range folders, slot folders and cells. The names of the plugin
+    /// releases without templates when not set.
+    #[serde(default)]
+    pub templates: KeyTemplates,
+    /// The compression method of the cells. When not set, the method giving the smallest
+    /// cell is picked for each cell.
+    #[serde(default)]
+    pub compression: Option<CompressionMethod>,
+    /// Write cells that are byte-for-byte identical across validators for the same block,
+    /// and record their content hashes. `compression` must be unset or the canonical method.
+    #[serde(default)]
+    pub canonical: bool,
+    /// Archive account updates in an `accounts` table. Disabled when not set.
+    #[serde(default)]
+    pub accounts: Option<AccountsConfig>,
+    /// Write a snapshot of the accounts notified while the validator loads its snapshot.
+    /// Disabled when not set.
+    #[serde(default)]
+    pub startup_snapshot: Option<StartupSnapshotConfig>,
+    /// Archive only the transactions selected by the filter. Every transaction is archived
+    /// when not set.
+    #[serde(default)]
+    pub transaction_filter: Option<TransactionFilterConfig>,
+    /// Store vote transactions in a `votes` table instead of the `blocks` cell.
+    #[serde(default)]
+    pub separate_votes: bool,
+    /// Local address of the Prometheus metrics endpoint. Metrics are not served when not set.
+    #[serde(default)]
+    pub metrics_address: Option<SocketAddr>,
+    /// Log filter of the plugin, e.g. `info` or `solana_cos_plugin=debug`. `RUST_LOG` is used
+    /// when not set.
+    #[serde(default)]
+    pub log_level: Option<String>,
+    /// Local address of the admin API. The admin API is disabled when not set.
+    #[serde(default)]
+    pub admin_address: Option<SocketAddr>,
+    /// Seconds between two updates of `status.json` in the workspace. The file is not
+    /// written when set to 0.
+    #[serde(default = "default_status_interval_secs")]
+    pub status_interval_secs: u64,
+    /// Thresholds after which a notification stream is considered stalled.
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
+    /// Write traces of the slot lifecycle as JSON lines to a dedicated file. Disabled when
+    /// not set.
+    #[serde(default)]
+    pub tracing: Option<TracingConfig>,
+}
+
+/// Selects the accounts whose updates are archived. When both lists are empty, the updates
+/// of every account are archived.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccountsConfig {
+    /// Archive updates to accounts owned by these programs.
+    #[serde(default)]
+    pub owners: Vec<String>,
+    /// Archive updates to these accounts.
+    #[serde(default)]
+    pub pubkeys: Vec<String>,
+}
+
+/// Selects the archived transactions by the programs they invoke and the accounts they
+/// reference. Empty include lists select every transaction.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransactionFilterConfig {
+    /// Archive the transactions invoking one of these programs.
+    #[serde(default)]
+    pub include_programs: Vec<String>,
+    /// Leave out the transactions invoking one of these programs.
+    #[serde(default)]
+    pub exclude_programs: Vec<String>,
+    /// Archive the transactions referencing one of these accounts.
+    #[serde(default)]
+    pub include_accounts: Vec<String>,
+    /// Leave out the transactions referencing one of these accounts.
+    #[serde(default)]
+    pub exclude_accounts: Vec<String>,
+    /// Leave out vote transactions.
+    #[serde(default)]
+    pub exclude_votes: bool,
+}
+
+/// Seconds without notification of each kind after which the stream is considered stalled.
+/// A kind of notification is not monitored when its threshold is 0.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchdogConfig {
+    #[serde(default = "default_watchdog_short_secs")]
+    pub slot_status_secs: u64,
+    #[serde(default = "default_watchdog_long_secs")]
+    pub rooted_secs: u64,
+    #[serde(default = "default_watchdog_long_secs")]
+    pub transaction_secs: u64,
+    #[serde(default = "default_watchdog_short_secs")]
+    pub entry_secs: u64,
+    #[serde(default = "default_watchdog_long_secs")]
+    pub block_metadata_secs: u64,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            slot_status_secs: default_watchdog_short_secs(),
+            rooted_secs: default_watchdog_long_secs(),
+            transaction_secs: default_watchdog_long_secs(),
+            entry_secs: default_watchdog_short_secs(),
+            block_metadata_secs: default_watchdog_long_secs(),
+        }
+    }
+}
+
+fn default_watchdog_short_secs() -> u64 {
+    30
+}
+
+fn default_watchdog_long_secs() -> u64 {
+    60
+}
+
+/// Destination and verbosity of the slot lifecycle traces.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TracingConfig {
+    /// Path of the file the traces are appended to.
+    pub path: String,
+    /// Trace filter, e.g. `info` or `solana_cos_plugin=debug`.
+    #[serde(default = "default_tracing_filter")]
+    pub filter: String,
+}
+
+fn default_tracing_filter() -> String {
+    "info".to_string()
+}
+
+/// Settings of the startup account snapshot.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StartupSnapshotConfig {
+    /// Number of accounts per chunk file.
+    #[serde(default = "default_snapshot_chunk_size")]
+    pub chunk_size: usize,
+}
+
+fn default_snapshot_chunk_size() -> usize {
+    100_000
+}
+
+fn default_status_interval_secs() -> u64 {
+    10
+}
+
+impl GeyserPluginCosConfig {
+    /// Hash of the settings, identifying the configuration the plugin runs with.
+    pub fn version(&self) -> String {
+        let config = serde_json::to_vec(self).unwrap_or_default();
+        solana_sdk::hash::hash(&config).to_string()
+    }
+
+    /// Read the configuration from a JSON, TOML or YAML file, picked by extension, apply the
+    /// `COS_PLUGIN_*` environment variables and validate it.
+    pub fn load(config_file: &str) -> Result<Self> {
+        let mut file = File::open(config_file)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let mut config = Self::parse(config_file, &contents)?;
+        let mut env_vars: Vec<_> = std::env::vars()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .collect();
+        env_vars.sort();
+        apply_env_overrides(&mut config, env_vars)?;
+        let mut config: Self = serde_json::from_value(config).map_err(|err| {
+            log::error!("COS: The config file is not in the format expected: {err}");
+            GeyserPluginError::ConfigFileReadError {
+                msg: format!("COS: The config file is not in the format expected: {err}"),
+            }
+        })?;
+        config.resolve_cluster()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Derives the genesis hash from the ledger and the cluster name from the genesis hash,
+    /// when they are not set.
+    pub fn resolve_cluster(&mut self) -> Result<()> {
+        if self.genesis_hash.is_none() {
+            if let Some(ledger_path) = &self.ledger_path {
+                let genesis_hash = ledger_genesis_hash(Path::new(ledger_path))
+                    .map_err(|err| invalid_field("ledger_path", &err.to_string()))?;
+                self.genesis_hash = Some(genesis_hash.to_string());
+            }
+        }
+        if self.cluster.is_none() {
+            self.cluster = self
+                .genesis_hash
+                .as_deref()
+                .and_then(cluster_name)
+                .map(str::to_string);
+        }
+        Ok(())
+    }
+
+    /// Parse the settings in the format given by the file extension, JSON by default.
+    fn parse(config_file: &str, contents: &str) -> Result<Value> {
+        let extension = Path::new(config_file)
+            .extension()
+            .and_then(|extension| extension.to_str());
+        let (format, config) = match extension {
+            Some("toml") => (
+                "TOML",
+                toml::from_str(contents).map_err(|err| err.to_string()),
+            ),
+            Some("yaml" | "yml") => (
+                "YAML",
+                serde_yaml::from_str(contents).map_err(|err| err.to_string()),
+            ),
+            _ => (
+                "JSON",
+                serde_json::from_str(contents).map_err(|err| err.to_string()),
+            ),
+        };
+        config.map_err(|err| {
+            log::error!("COS: The config file is not valid {format}: {err}");
+            GeyserPluginError::ConfigFileReadError {
+                msg: format!("COS: The config file is not valid {format}: {err}"),
+            }
+        })
+    }
+
+    /// Reverts the settings that cannot change while the plugin runs to their `previous`
+    /// values, and returns the names of the reverted fields.
+    pub fn keep_unsafe_fields(&mut self, previous: &Self) -> Vec<&'static str> {
+        let mut reverted = vec![];
+        if self.workspace != previous.workspace {
+            self.workspace = previous.workspace.clone();
+            reverted.push("workspace");
+        }
+        if self.slot_range != previous.slot_range {
+            self.slot_range = previous.slot_range;
+            reverted.push("slot_range");
+        }
+        if self.range_policy != previous.range_policy {
+            self.range_policy = previous.range_policy.clone();
+            reverted.push("range_policy");
+        }
+        if self.start_slot != previous.start_slot {
+            self.start_slot = previous.start_slot;
+            reverted.push("start_slot");
+        }
+        if self.end_slot != previous.end_slot {
+            self.end_slot = previous.end_slot;
+            reverted.push("end_slot");
+        }
+        if self.cluster != previous.cluster {
+            self.cluster = previous.cluster.clone();
+            reverted.push("cluster");
+        }
+        if self.genesis_hash != previous.genesis_hash {
+            self.genesis_hash = previous.genesis_hash.clone();
+            reverted.push("genesis_hash");
+        }
+        if self.identity != previous.identity {
+            self.identity = previous.identity.clone();
+            reverted.push("identity");
+        }
+        if self.templates != previous.templates {
+            self.templates = previous.templates.clone();
+            reverted.push("templates");
+        }
+        reverted
+    }
+
+    /// The layout of the workspace written with these settings.
+    pub fn layout(&self) -> WorkspaceLayout {
+        WorkspaceLayout {
+            slot_range: self.slot_range,
+            range_policy: self.range_policy.clone(),
+            templates: self.templates.clone(),
+            cluster: self.cluster.clone(),
+            genesis_hash: self.genesis_hash.clone(),
+            identity: self.identity.clone(),
+        }
+    }
+
+    /// Check the values of the settings. Every error names the invalid field.
+    pub fn validate(&self) -> Result<()> {
+        if self.workspace.is_empty() {
+            return Err(invalid_field("workspace", "must not be empty"));
+        }
+        if self.slot_range == 0 {
+            return Err(invalid_field("slot_range", "must be greater than 0"));
+        }
+        if self.commit_slot_delay >= self.slot_range {
+            return Err(invalid_field(
+                "commit_slot_delay",
+                &format!(
+                    "must be less than slot_range ({}), or ranges are never committed",
+                    self.slot_range
+                ),
+            ));
+        }
+        match self.range_policy {
+            RangePolicy::Epoch {
+                slots_per_epoch, ..
+            } if slots_per_epoch < MINIMUM_SLOTS_PER_EPOCH => {
+                return Err(invalid_field(
+                    "range_policy.slots_per_epoch",
+                    &format!("must be at least {MINIMUM_SLOTS_PER_EPOCH}"),
+                ));
+            }
+            RangePolicy::Size { target_bytes: 0 } => {
+                return Err(invalid_field(
+                    "range_policy.target_bytes",
+                    "must be greater than 0",
+                ));
+            }
+            _ => {}
+        }
+        if let Some(compression) = self
+            .compression
+            .filter(|compression| self.canonical && *compression != CANONICAL_COMPRESSION_METHOD)
+        {
+            return Err(invalid_field(
+                "compression",
+                &format!(
+                    "must be {CANONICAL_COMPRESSION_METHOD:?} with canonical, not {compression:?}, \
+                     so that every validator writes the same cells"
+                ),
+            ));
+        }
+        if let (Some(start_slot), Some(end_slot)) = (self.start_slot, self.end_slot) {
+            if end_slot < start_slot {
+                return Err(invalid_field(
+                    "end_slot",
+                    &format!("must not be less than start_slot ({start_slot})"),
+                ));
+            }
+        }
+        if let Some(genesis_hash) = &self.genesis_hash {
+            if let Err(err) = Hash::from_str(genesis_hash) {
+                return Err(invalid_field("genesis_hash", &err.to_string()));
+            }
+            if let Some(cluster) = &self.cluster {
+                if let Some(known) =
+                    known_genesis_hash(cluster).filter(|known| known != genesis_hash)
+                {
+                    return Err(invalid_field(
+                        "cluster",
+                        &format!(
+                            "{cluster} has the genesis hash {known}, not {genesis_hash}; check \
+                             the cluster of the validator"
+                        ),
+                    ));
+                }
+            }
+        }
+        if let Some(identity) = &self.identity {
+            parse_pubkeys("identity", std::slice::from_ref(identity))?;
+        }
+        if let Err((field, msg)) = self.layout().naming() {
+            return Err(invalid_field(&field, &msg));
+        }
+        if let Some(accounts) = &self.accounts {
+            parse_pubkeys("accounts.owners", &accounts.owners)?;
+            parse_pubkeys("accounts.pubkeys", &accounts.pubkeys)?;
+        }
+        if let Some(startup_snapshot) = &self.startup_snapshot {
+            if startup_snapshot.chunk_size == 0 {
+                return Err(invalid_field(
+                    "startup_snapshot.chunk_size",
+                    "must be greater than 0",
+                ));
+            }
+        }
+        if let Some(filter) = &self.transaction_filter {
+            parse_pubkeys(
+                "transaction_filter.include_programs",
+                &filter.include_programs,
+            )?;
+            parse_pubkeys(
+                "transaction_filter.exclude_programs",
+                &filter.exclude_programs,
+            )?;
+            parse_pubkeys(
+                "transaction_filter.include_accounts",
+                &filter.include_accounts,
+            )?;
+            parse_pubkeys(
+                "transaction_filter.exclude_accounts",
+                &filter.exclude_accounts,
+            )?;
+        }
+        if self.metrics_address.is_some() && self.metrics_address == self.admin_address {
+            return Err(invalid_field(
+                "admin_address",
+                "must differ from metrics_address",
+            ));
+        }
+        if let Some(tracing) = &self.tracing {
+            if tracing.path.is_empty() {
+                return Err(invalid_field("tracing.path", "must not be empty"));
+            }
+            if let Err(err) = tracing.filter.parse::<Targets>() {
+                return Err(invalid_field("tracing.filter", &err.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the workspace can be written to, creating it if needed, so that a
+    /// misconfigured workspace fails the plugin load rather than the first save.
+    pub fn check_workspace(&self) -> Result<()> {
+        let workspace = Path::new(&self.workspace);
+        let probe_path = workspace.join(".write_probe");
+        fs::create_dir_all(workspace)
+            .and_then(|_| fs::write(&probe_path, b""))
+            .and_then(|_| fs::remove_file(&probe_path))
+            .map_err(|err| {
+                invalid_field(
+                    "workspace",
+                    &format!("{} is not writable: {err}", self.workspace),
+                )
+            })
+    }
+}
+
+/// Settings of type string, whose environment variables are taken as strings even when they
+/// are valid JSON, e.g. `COS_PLUGIN_CLUSTER=1`. A `*` field matches any field.
+const STRING_SETTINGS: &[&str] = &[
+    "libpath",
+    "workspace",
+    "cluster",
+    "genesis_hash",
+    "ledger_path",
+    "identity",
+    "log_level",
+    "templates__range_folder",
+    "templates__slot_folder",
+    "templates__cell",
+    "templates__tables__*",
+    "tracing__path",
+    "tracing__filter",
+];
+
+fn is_string_setting(key: &str) -> bool {
+    STRING_SETTINGS.iter().any(|setting| {
+        let mut fields = key.split("__");
+        setting.split("__").all(|field| {
+            fields
+                .next()
+                .is_some_and(|key| field == "*" || field == key)
+        }) && fields.next().is_none()
+    })
+}
+
+/// Overrides the settings with the `COS_PLUGIN_<FIELD>` environment variables. Nested fields
+/// are separated by a double underscore, e.g. `COS_PLUGIN_WATCHDOG__ROOTED_SECS`. Values of
+/// string settings are taken as is, and the other values are parsed as JSON when they are
+/// valid JSON, and taken as strings otherwise. With the `_FILE` suffix, the value is read
+/// from the file the variable points to, e.g. a mounted secret.
+fn apply_env_overrides(config: &mut Value, env_vars: Vec<(String, String)>) -> Result<()> {
+    for (name, value) in env_vars {
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let (key, value) = match key.strip_suffix("_FILE") {
+            Some(key) => {
+                let contents = fs::read_to_string(&value).map_err(|err| {
+                    GeyserPluginError::ConfigFileReadError {
+                        msg: format!("COS: Failed to read {name} from {value}: {err}"),
+                    }
+                })?;
+                (key, contents.trim_end().to_string())
+            }
+            None => (key, value),
+        };
+        let key = key.to_lowercase();
+        let value = if is_string_setting(&key) {
+            Value::String(value)
+        } else {
+            serde_json::from_str(&value).unwrap_or(Value::String(value))
+        };
+        let mut fields: Vec<_> = key.split("__").collect();
+        let last_field = fields.pop().unwrap_or_default();
+        let mut object = &mut *config;
+        for field in fields {
+            object = as_object(object).entry(field).or_insert(Value::Null);
+        }
+        as_object(object).insert(last_field.to_string(), value);
+        log::info!("COS: Setting {key} from the environment variable {name}");
+    }
+    Ok(())
+}
+
+/// The fields of `value`, replacing it with an empty object if it is not an object.
+fn as_object(value: &mut Value) -> &mut Map<String, Value> {
+    if !value.is_object() {
+        *value = Value::Object(Map::new());
+    }
+    match value {
+        Value::Object(map) => map,
+        _ => unreachable!(),
+    }
+}
+
+fn invalid_field(field: &str, msg: &str) -> GeyserPluginError {
+    log::error!("COS: Invalid {field} in the config file: {msg}");
+    GeyserPluginError::ConfigFileReadError {
+        msg: format!("COS: Invalid {field} in the config file: {msg}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json};
+
+    fn env_var(name: &str, value: &str) -> (String, String) {
+        (format!("{ENV_PREFIX}{name}"), value.to_string())
+    }
+
+    #[test]
+    fn test_env_overrides_follow_the_type_of_the_settings() {
+        let mut config = json!({"workspace": "/data/workspace", "commit_slot_delay": 5});
+        apply_env_overrides(
+            &mut config,
+            vec![
+                env_var("SLOT_RANGE", "1000"),
+                env_var("CLUSTER", "1"),
+                env_var("IDENTITY", "null"),
+                env_var("LOG_LEVEL", "true"),
+                env_var("START_SLOT", "null"),
+                env_var("CANONICAL", "true"),
+                env_var("WATCHDOG__ROOTED_SECS", "120"),
+                env_var("TEMPLATES__TABLES__BLOCKS", "1"),
+            ],
+        )
+        .unwrap();
+        let config: GeyserPluginCosConfig = serde_json::from_value(config).unwrap();
+        assert_eq!(config.slot_range, 1000);
+        assert_eq!(config.cluster.as_deref(), Some("1"));
+        assert_eq!(config.identity.as_deref(), Some("null"));
+        assert_eq!(config.log_level.as_deref(), Some("true"));
+        assert_eq!(config.start_slot, None);
+        assert!(config.canonical);
+        assert_eq!(config.watchdog.rooted_secs, 120);
+        assert_eq!(config.templates.tables["blocks"], "1");
+    }
+
+    #[test]
+    fn test_env_overrides_read_files() {
+        let path = std::env::temp_dir().join(format!("cos-env-file-{}", std::process::id()));
+        fs::write(&path, "42\n").unwrap();
+        let mut config = json!({});
+        apply_env_overrides(
+            &mut config,
+            vec![
+                env_var("COMMIT_SLOT_DELAY_FILE", path.to_str().unwrap()),
+                env_var("GENESIS_HASH_FILE", path.to_str().unwrap()),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            json!({"commit_slot_delay": 42, "genesis_hash": "42"})
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_canonical_requires_the_canonical_compression() {
+        let config = |compression: Value| -> GeyserPluginCosConfig {
+            serde_json::from_value(json!({
+                "workspace": "/data/workspace",
+                "slot_range": 10,
+                "commit_slot_delay": 5,
+                "canonical": true,
+                "compression": compression,
+            }))
+            .unwrap()
+        };
+        config(Value::Null).validate().unwrap();
+        config(json!("Bzip2")).validate().unwrap();
+        assert!(config(json!("Zstd")).validate().is_err());
+        assert!(config(json!("NoCompression")).validate().is_err());
+    }
+
+    #[test]
+    fn test_string_settings_are_strings() {
+        let mut config = json!({"workspace": "", "slot_range": 1, "commit_slot_delay": 0});
+        let env_vars = STRING_SETTINGS
+            .iter()
+            .map(|setting| env_var(&setting.replace('*', "blocks").to_uppercase(), "1"))
+            .collect();
+        apply_env_overrides(&mut config, env_vars).unwrap();
+        serde_json::from_value::<GeyserPluginCosConfig>(config).unwrap();
+    }
 }
@@ -0,0 +1,160 @@
+/// Minimal HTTP/1.1 server for the local metrics and admin endpoints.
+use std::{
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Largest request body accepted.
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+pub(crate) struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// Value of the header `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub(crate) struct HttpResponse {
+    pub status: &'static str,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn not_found() -> Self {
+        Self {
+            status: "404 Not Found",
+            content_type: "text/plain",
+            body: vec![],
+        }
+    }
+}
+
+/// Serves one request per connection on a background thread. Stopped when dropped.
+pub(crate) struct HttpServer {
+    /// Address the server listens on, with the port picked by the system if 0 was given.
+    local_address: SocketAddr,
+    exit: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HttpServer {
+    pub fn start<F>(name: &str, address: SocketAddr, handler: F) -> io::Result<Self>
+    where
+        F: Fn(&HttpRequest) -> HttpResponse + Send + 'static,
+    {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let local_address = listener.local_addr()?;
+
+        let exit = Arc::new(AtomicBool::new(false));
+        let thread_exit = exit.clone();
+        let thread = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || {
+                while !thread_exit.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Err(err) = Self::serve(stream, &handler) {
+                                log::debug!("COS: HTTP request failed: {err}");
+                            }
+                        }
+                        Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                            thread::sleep(Duration::from_millis(100));
+                        }
+                        Err(err) => log::warn!("COS: HTTP server accept failed: {err}"),
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            local_address,
+            exit,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn local_address(&self) -> SocketAddr {
+        self.local_address
+    }
+
+    fn serve<F>(mut stream: TcpStream, handler: &F) -> io::Result<()>
+    where
+        F: Fn(&HttpRequest) -> HttpResponse,
+    {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut reader = BufReader::new(&stream);
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let path = parts.next().unwrap_or_default().to_string();
+
+        let mut headers = vec![];
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_string(), value.trim().to_string()));
+            }
+        }
+
+        let mut request = HttpRequest {
+            method,
+            path,
+            headers,
+            body: vec![],
+        };
+        let content_length = request
+            .header("Content-Length")
+            .and_then(|value| value.parse::<usize>().ok())
+            .unwrap_or_default();
+        if content_length > MAX_BODY_SIZE {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("request body of {content_length} bytes is too large"),
+            ));
+        }
+        request.body.resize(content_length, 0);
+        reader.read_exact(&mut request.body)?;
+
+        let response = handler(&request);
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            response.status,
+            response.content_type,
+            response.body.len()
+        )?;
+        stream.write_all(&response.body)
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.exit.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
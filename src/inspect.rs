@@ -0,0 +1,717 @@
+/// Commands of the `cos-inspect` tool.
+use {
+    crate::{
+        accounts::AccountUpdates,
+        compression::{compression_method, decompress},
+        reader::{SlotCells, SlotRange, WorkspaceReader},
+        votes::VoteTransactions,
+    },
+    prost::Message,
+    solana_sdk::{clock::Slot, hash::hash, pubkey::Pubkey, signature::Signature},
+    solana_storage_proto::convert::{entries, generated},
+    solana_transaction_status::{
+        BlockEncodingOptions, ConfirmedBlock, TransactionDetails, TransactionWithStatusMeta,
+        UiTransactionEncoding,
+    },
+    std::{
+        collections::{BTreeMap, HashSet},
+        fs,
+        io::{self, ErrorKind, Write},
+    },
+};
+
+#[derive(Serialize)]
+struct EntryJson {
+    index: usize,
+    num_hashes: u64,
+    hash: String,
+    num_transactions: u64,
+    starting_transaction_index: usize,
+}
+
+#[derive(Serialize)]
+struct AccountJson {
+    pubkey: String,
+    owner: String,
+    lamports: u64,
+    executable: bool,
+    rent_epoch: u64,
+    data_len: usize,
+    write_version: u64,
+    txn_signature: Option<String>,
+}
+
+#[derive(Default)]
+struct CompressionStats {
+    cells: u64,
+    compressed_bytes: u64,
+    uncompressed_bytes: u64,
+}
+
+/// Prints one line per slot range.
+pub fn list_ranges(reader: &WorkspaceReader, out: &mut dyn Write) -> io::Result<()> {
+    for range in reader.ranges()? {
+        let slots = reader.slots(&range)?;
+        writeln!(
+            out,
+            "{:<8} {:>12} {:>12} {:>6} slots  {}",
+            range.location,
+            range.start,
+            range.end,
+            slots.len(),
+            range.path.display()
+        )?;
+    }
+    Ok(())
+}
+
+/// Prints one line per slot of the range containing `slot`.
+pub fn list_slots(reader: &WorkspaceReader, slot: Slot, out: &mut dyn Write) -> io::Result<()> {
+    let ranges = reader.ranges()?;
+    let ranges: Vec<_> = ranges.iter().filter(|range| range.contains(slot)).collect();
+    if ranges.is_empty() {
+        return Err(not_found(format!("no range contains slot {slot}")));
+    }
+    for range in ranges {
+        writeln!(
+            out,
+            "{} range {}..{}",
+            range.location, range.start, range.end
+        )?;
+        for slot_cells in reader.slots(range)? {
+            let mut tables = vec![];
+            for table in slot_cells.tables()? {
+                let size = match slot_cells.cell_path(&table) {
+                    Some(path) => fs::metadata(path)?.len(),
+                    None => 0,
+                };
+                tables.push(format!("{table}={size}"));
+            }
+            writeln!(out, "{:>12}  {}", slot_cells.slot, tables.join(" "))?;
+        }
+    }
+    Ok(())
+}
+
+/// Dumps the decoded `blocks` cell of `slot` as JSON.
+pub fn dump_block(reader: &WorkspaceReader, slot: Slot, out: &mut dyn Write) -> io::Result<()> {
+    let slot_cells = reader
+        .find_slot(slot)?
+        .ok_or_else(|| not_found(format!("slot {slot} not found")))?;
+    let block = WorkspaceReader::read_block(&slot_cells)?
+        .ok_or_else(|| not_found(format!("slot {slot} has no blocks cell")))?;
+    let block = block
+        .encode_with_options(
+            UiTransactionEncoding::Json,
+            BlockEncodingOptions {
+                transaction_details: TransactionDetails::Full,
+                show_rewards: true,
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .map_err(|err| io::Error::new(ErrorKind::InvalidData, format!("{err}")))?;
+    serde_json::to_writer_pretty(&mut *out, &block)?;
+    writeln!(out)
+}
+
+/// Dumps the decoded `entries` cell of `slot` as JSON.
+pub fn dump_entries(reader: &WorkspaceReader, slot: Slot, out: &mut dyn Write) -> io::Result<()> {
+    let slot_cells = reader
+        .find_slot(slot)?
+        .ok_or_else(|| not_found(format!("slot {slot} not found")))?;
+    let entries = WorkspaceReader::read_entries(&slot_cells)?
+        .ok_or_else(|| not_found(format!("slot {slot} has no entries cell")))?;
+    let entries: Vec<_> = entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| EntryJson {
+            index,
+            num_hashes: entry.num_hashes,
+            hash: entry.hash.to_string(),
+            num_transactions: entry.num_transactions,
+            starting_transaction_index: entry.starting_transaction_index,
+        })
+        .collect();
+    serde_json::to_writer_pretty(&mut *out, &entries)?;
+    writeln!(out)
+}
+
+/// Dumps the decoded `accounts` cell of `slot` as JSON. Account data is summarized by its
+/// length.
+pub fn dump_accounts(reader: &WorkspaceReader, slot: Slot, out: &mut dyn Write) -> io::Result<()> {
+    let slot_cells = reader
+        .find_slot(slot)?
+        .ok_or_else(|| not_found(format!("slot {slot} not found")))?;
+    let accounts = WorkspaceReader::read_accounts(&slot_cells)?
+        .ok_or_else(|| not_found(format!("slot {slot} has no accounts cell")))?;
+    let accounts: Vec<_> = accounts
+        .accounts
+        .into_iter()
+        .map(|account| AccountJson {
+            pubkey: bytes_to_string::<Pubkey>(&account.pubkey),
+            owner: bytes_to_string::<Pubkey>(&account.owner),
+            lamports: account.lamports,
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data_len: account.data.len(),
+            write_version: account.write_version,
+            txn_signature: account
+                .txn_signature
+                .map(|signature| bytes_to_string::<Signature>(&signature)),
+        })
+        .collect();
+    serde_json::to_writer_pretty(&mut *out, &accounts)?;
+    writeln!(out)
+}
+
+fn bytes_to_string<T>(bytes: &[u8]) -> String
+where
+    T: for<'a> TryFrom<&'a [u8]> + ToString,
+{
+    T::try_from(bytes)
+        .map(|value| value.to_string())
+        .unwrap_or_else(|_| format!("<invalid {} bytes>", bytes.len()))
+}
+
+/// Prints compression statistics per table and compression method.
+pub fn compression_stats(reader: &WorkspaceReader, out: &mut dyn Write) -> io::Result<()> {
+    let mut stats: BTreeMap<(String, String), CompressionStats> = BTreeMap::new();
+    for range in reader.ranges()? {
+        for slot_cells in reader.slots(&range)? {
+            for table in slot_cells.tables()? {
+                let Some(path) = slot_cells.cell_path(&table) else {
+                    continue;
+                };
+                let data = fs::read(path)?;
+                let method = compression_method(&data)?;
+                let stats = stats.entry((table, format!("{method:?}"))).or_default();
+                stats.cells += 1;
+                stats.compressed_bytes += data.len() as u64;
+                stats.uncompressed_bytes += decompress(&data)?.len() as u64;
+            }
+        }
+    }
+
+    writeln!(
+        out,
+        "{:<10} {:<14} {:>10} {:>16} {:>16} {:>7}",
+        "table", "method", "cells", "compressed", "uncompressed", "ratio"
+    )?;
+    for ((table, method), stats) in stats {
+        let ratio = if stats.compressed_bytes > 0 {
+            stats.uncompressed_bytes as f64 / stats.compressed_bytes as f64
+        } else {
+            0.0
+        };
+        writeln!(
+            out,
+            "{:<10} {:<14} {:>10} {:>16} {:>16} {:>7.2}",
+            table, method, stats.cells, stats.compressed_bytes, stats.uncompressed_bytes, ratio
+        )?;
+    }
+    Ok(())
+}
+
+/// Prints one line per startup snapshot.
+pub fn list_snapshots(reader: &WorkspaceReader, out: &mut dyn Write) -> io::Result<()> {
+    for (path, manifest) in reader.snapshots()? {
+        writeln!(
+            out,
+            "{:>12} {:>12} accounts {:>20} lamports {:>6} chunks  {}",
+            manifest.slot,
+            manifest.accounts,
+            manifest.lamports,
+            manifest.chunks.len(),
+            path.display()
+        )?;
+    }
+    Ok(())
+}
+
+/// Checks that every cell decompresses and decodes, and reports missing or extra slots in
+/// each range. Startup snapshots are checked against their manifest, and against the genesis
+/// hash of the workspace. Returns the number of problems found.
+pub fn verify(reader: &WorkspaceReader, out: &mut dyn Write) -> io::Result<usize> {
+    let mut problems = 0;
+    for range in reader.ranges()? {
+        problems += verify_range(reader, &range, out)?;
+    }
+    for (path, manifest) in reader.snapshots()? {
+        for problem in manifest.verify(&path)? {
+            problems += 1;
+            writeln!(out, "{}: {problem}", path.display())?;
+        }
+        let workspace_genesis_hash = reader
+            .layout()
+            .and_then(|layout| layout.genesis_hash.as_ref());
+        if let (Some(genesis_hash), Some(workspace_genesis_hash)) =
+            (&manifest.genesis_hash, workspace_genesis_hash)
+        {
+            if genesis_hash != workspace_genesis_hash {
+                problems += 1;
+                writeln!(
+                    out,
+                    "{}: snapshot of genesis hash {genesis_hash}, not the genesis hash \
+                     {workspace_genesis_hash} of the workspace",
+                    path.display()
+                )?;
+            }
+        }
+    }
+    writeln!(out, "{problems} problem(s) found")?;
+    Ok(problems)
+}
+
+fn verify_range(
+    reader: &WorkspaceReader,
+    range: &SlotRange,
+    out: &mut dyn Write,
+) -> io::Result<usize> {
+    let mut problems = 0;
+    let slots = reader.slots(range)?;
+    let present: HashSet<Slot> = slots.iter().map(|slot_cells| slot_cells.slot).collect();
+    let mut parents = vec![];
+
+    if let Some(layout) = reader.layout() {
+        if !layout.is_valid_range(range.start, range.end) {
+            problems += 1;
+            writeln!(
+                out,
+                "{}: range does not match the slot_range {} and range_policy {:?} of the \
+                 workspace",
+                range.path.display(),
+                layout.slot_range,
+                layout.range_policy
+            )?;
+        }
+    }
+
+    for slot_cells in &slots {
+        let slot = slot_cells.slot;
+        if !range.contains(slot) {
+            problems += 1;
+            writeln!(
+                out,
+                "{}: extra slot {slot} outside of range",
+                range.path.display()
+            )?;
+        }
+        for table in slot_cells.tables()? {
+            if let Err(err) = verify_cell(slot_cells, &table, &mut parents) {
+                problems += 1;
+                writeln!(out, "{}: slot {slot} {table}: {err}", range.path.display())?;
+            }
+        }
+        for problem in verify_content_hashes(slot_cells)? {
+            problems += 1;
+            writeln!(out, "{}: slot {slot} {problem}", range.path.display())?;
+        }
+        if let Some(problem) = verify_transaction_counts(slot_cells)? {
+            problems += 1;
+            writeln!(out, "{}: slot {slot} {problem}", range.path.display())?;
+        }
+        if slot_cells.cell_path("blocks").is_none() {
+            problems += 1;
+            writeln!(
+                out,
+                "{}: slot {slot} has no blocks cell",
+                range.path.display()
+            )?;
+        }
+    }
+
+    // Skipped slots are expected, so a slot is only reported as missing when a stored
+    // block names it as its parent.
+    let mut missing: Vec<Slot> = parents
+        .into_iter()
+        .filter(|parent| range.contains(*parent) && !present.contains(parent))
+        .collect();
+    missing.sort_unstable();
+    missing.dedup();
+    for slot in missing {
+        problems += 1;
+        writeln!(out, "{}: missing slot {slot}", range.path.display())?;
+    }
+    Ok(problems)
+}
+
+fn verify_cell(slot_cells: &SlotCells, table: &str, parents: &mut Vec<Slot>) -> io::Result<()> {
+    let path = slot_cells
+        .cell_path(table)
+        .ok_or_else(|| not_found("cell file not found".to_string()))?;
+    let data = decompress(&fs::read(path)?)?;
+    match table {
+        "blocks" => {
+            let block = generated::ConfirmedBlock::decode(&data[..]).map_err(invalid_data)?;
+            parents.push(block.parent_slot);
+            ConfirmedBlock::try_from(block).map_err(invalid_data)?;
+        }
+        "entries" => {
+            entries::Entries::decode(&data[..]).map_err(invalid_data)?;
+        }
+        "accounts" => {
+            AccountUpdates::decode(&data[..]).map_err(invalid_data)?;
+        }
+        "votes" => {
+            let votes = VoteTransactions::decode(&data[..]).map_err(invalid_data)?;
+            for vote in votes.transactions {
+                let transaction = vote
+                    .transaction
+                    .filter(|transaction| transaction.transaction.is_some())
+                    .ok_or_else(|| invalid_data("vote without transaction"))?;
+                TransactionWithStatusMeta::try_from(transaction).map_err(invalid_data)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Checks the transaction counts recorded when a transaction filter is configured, so that
+/// filtered transactions can be told apart from lost ones.
+fn verify_transaction_counts(slot_cells: &SlotCells) -> io::Result<Option<String>> {
+    let Some(counts) = slot_cells.transaction_counts()? else {
+        return Ok(None);
+    };
+    if counts.archived + counts.filtered != counts.executed {
+        return Ok(Some(format!(
+            "transaction counts mismatch: {} executed, {} archived, {} filtered",
+            counts.executed, counts.archived, counts.filtered
+        )));
+    }
+    let Ok(Some(block)) = WorkspaceReader::read_block(slot_cells) else {
+        // Decode failures are reported with the cell.
+        return Ok(None);
+    };
+    if block.transactions.len() as u64 != counts.archived {
+        return Ok(Some(format!(
+            "{} transaction(s) in blocks cell, {} archived",
+            block.transactions.len(),
+            counts.archived
+        )));
+    }
+    Ok(None)
+}
+
+/// Checks the cells of a slot against the content hashes recorded in canonical mode.
+fn verify_content_hashes(slot_cells: &SlotCells) -> io::Result<Vec<String>> {
+    let mut problems = vec![];
+    let Some(content_hashes) = slot_cells.content_hashes()? else {
+        return Ok(problems);
+    };
+    for (cell, expected_hash) in content_hashes {
+        let table = cell.split('/').next().unwrap_or_default();
+        match WorkspaceReader::read_cell(slot_cells, table) {
+            Ok(Some(data)) => {
+                let content_hash = hash(&data).to_string();
+                if content_hash != expected_hash {
+                    problems.push(format!(
+                        "{cell}: content hash {content_hash} does not match {expected_hash}"
+                    ));
+                }
+            }
+            Ok(None) => problems.push(format!("{cell}: has a content hash but no cell")),
+            // Reported by `verify_cell`
+            Err(_) => {}
+        }
+    }
+    Ok(problems)
+}
+
+fn not_found(msg: String) -> io::Error {
+    io::Error::new(ErrorKind::NotFound, msg)
+}
+
+fn invalid_data<E: std::fmt::Display>(err: E) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("{err}"))
+}
+
+/// Compares the cells of two workspaces slot by slot, after decompression, and prints every
+/// difference. Slots outside of `first_slot..=last_slot` are ignored. Returns the number of
+/// differences found.
+pub fn diff(
+    reader: &WorkspaceReader,
+    other: &WorkspaceReader,
+    first_slot: Slot,
+    last_slot: Slot,
+    out: &mut dyn Write,
+) -> io::Result<usize> {
+    let slots = reader.all_slots()?;
+    let other_slots = other.all_slots()?;
+    let mut all_slots: Vec<Slot> = slots.keys().chain(other_slots.keys()).copied().collect();
+    all_slots.sort_unstable();
+    all_slots.dedup();
+
+    let mut differences = 0;
+    for slot in all_slots
+        .into_iter()
+        .filter(|slot| (first_slot..=last_slot).contains(slot))
+    {
+        let (slot_cells, other_slot_cells) = match (slots.get(&slot), other_slots.get(&slot)) {
+            (Some(slot_cells), Some(other_slot_cells)) => (slot_cells, other_slot_cells),
+            (Some(_), None) => {
+                differences += 1;
+                writeln!(out, "slot {slot}: missing from the second workspace")?;
+                continue;
+            }
+            (None, _) => {
+                differences += 1;
+                writeln!(out, "slot {slot}: missing from the first workspace")?;
+                continue;
+            }
+        };
+        for difference in diff_slot(slot_cells, other_slot_cells)? {
+            differences += 1;
+            writeln!(out, "slot {slot}: {difference}")?;
+        }
+    }
+    writeln!(out, "{differences} difference(s) found")?;
+    Ok(differences)
+}
+
+fn diff_slot(slot_cells: &SlotCells, other_slot_cells: &SlotCells) -> io::Result<Vec<String>> {
+    let mut differences = vec![];
+    let mut tables = slot_cells.tables()?;
+    tables.extend(other_slot_cells.tables()?);
+    tables.sort();
+    tables.dedup();
+
+    // Vote transactions are compared as part of the full block.
+    tables.retain(|table| table != "votes");
+
+    for table in tables {
+        let (data, other_data) = if table == "blocks" {
+            (
+                WorkspaceReader::read_block_cell(slot_cells)?,
+                WorkspaceReader::read_block_cell(other_slot_cells)?,
+            )
+        } else {
+            (
+                WorkspaceReader::read_cell(slot_cells, &table)?,
+                WorkspaceReader::read_cell(other_slot_cells, &table)?,
+            )
+        };
+        match (data, other_data) {
+            (Some(data), Some(other_data)) => {
+                if table == "blocks" {
+                    differences.extend(diff_blocks(&data, &other_data)?);
+                }
+                if let Some(offset) = first_difference(&data, &other_data) {
+                    differences.push(format!(
+                        "{table}: protobuf differs at byte {offset} ({} vs {} bytes)",
+                        data.len(),
+                        other_data.len()
+                    ));
+                }
+            }
+            (Some(_), None) => {
+                differences.push(format!("{table}: missing from the second workspace"))
+            }
+            (None, Some(_)) => {
+                differences.push(format!("{table}: missing from the first workspace"))
+            }
+            (None, None) => {}
+        }
+    }
+    Ok(differences)
+}
+
+fn diff_blocks(data: &[u8], other_data: &[u8]) -> io::Result<Vec<String>> {
+    let block = generated::ConfirmedBlock::decode(data).map_err(invalid_data)?;
+    let other_block = generated::ConfirmedBlock::decode(other_data).map_err(invalid_data)?;
+    let mut differences = vec![];
+    if block.transactions.len() != other_block.transactions.len() {
+        differences.push(format!(
+            "blocks: transaction count {} vs {}",
+            block.transactions.len(),
+            other_block.transactions.len()
+        ));
+    }
+    if block.blockhash != other_block.blockhash {
+        differences.push(format!(
+            "blocks: blockhash {} vs {}",
+            block.blockhash, other_block.blockhash
+        ));
+    }
+    if block.previous_blockhash != other_block.previous_blockhash {
+        differences.push(format!(
+            "blocks: previous blockhash {} vs {}",
+            block.previous_blockhash, other_block.previous_blockhash
+        ));
+    }
+    if block.parent_slot != other_block.parent_slot {
+        differences.push(format!(
+            "blocks: parent slot {} vs {}",
+            block.parent_slot, other_block.parent_slot
+        ));
+    }
+    Ok(differences)
+}
+
+fn first_difference(data: &[u8], other_data: &[u8]) -> Option<usize> {
+    data.iter()
+        .zip(other_data)
+        .position(|(byte, other_byte)| byte != other_byte)
+        .or_else(|| (data.len() != other_data.len()).then(|| data.len().min(other_data.len())))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            cos_types::CosVersionedConfirmedBlockWithEntries,
+            geyser_plugin_cos_config::GeyserPluginCosConfig,
+            storage::{Storage, StorageManager},
+        },
+        serde_json::json,
+        solana_sdk::hash::Hash,
+        std::path::{Path, PathBuf},
+    };
+
+    /// A workspace named after the test with ranges of 10 slots, holding `slots` with
+    /// blockhash `blockhash(slot)`. Every block names the previous one as its parent.
+    fn workspace(
+        name: &str,
+        slots: impl IntoIterator<Item = Slot>,
+        blockhash: impl Fn(Slot) -> Hash,
+    ) -> PathBuf {
+        let workspace =
+            std::env::temp_dir().join(format!("cos-inspect-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&workspace);
+        let config: GeyserPluginCosConfig = serde_json::from_value(json!({
+            "workspace": workspace,
+            "genesis_hash": Hash::default().to_string(),
+            "slot_range": 10,
+            "commit_slot_delay": 5,
+        }))
+        .unwrap();
+        let storage = StorageManager::new(&config).unwrap();
+        let mut parent_slot = None;
+        for slot in slots {
+            let parent_slot = parent_slot.replace(slot).unwrap_or(slot - 1);
+            let mut block = CosVersionedConfirmedBlockWithEntries::default();
+            block.block.parent_slot = parent_slot;
+            block.block.previous_blockhash = blockhash(parent_slot).to_string();
+            block.block.blockhash = blockhash(slot).to_string();
+            storage.save(slot, &block).unwrap();
+        }
+        storage.commit_all().unwrap();
+        workspace
+    }
+
+    fn blockhash(slot: Slot) -> Hash {
+        Hash::new_from_array([slot as u8; 32])
+    }
+
+    fn output(run: impl FnOnce(&mut Vec<u8>) -> io::Result<usize>) -> (usize, Vec<String>) {
+        let mut out = vec![];
+        let count = run(&mut out).unwrap();
+        let lines = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        (count, lines)
+    }
+
+    fn cell_path(workspace: &Path, slot: Slot, table: &str) -> PathBuf {
+        let reader = WorkspaceReader::open(workspace).unwrap();
+        reader
+            .find_slot(slot)
+            .unwrap()
+            .unwrap()
+            .cell_path(table)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_list_ranges() {
+        let workspace = workspace("list", (10..=24).filter(|slot| *slot != 13), blockhash);
+        let reader = WorkspaceReader::open(&workspace).unwrap();
+        let mut out = vec![];
+        list_ranges(&reader, &mut out).unwrap();
+        let lines: Vec<_> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                line.split_whitespace()
+                    .take(5)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec!["storage 10 20 9 slots", "storage 20 30 5 slots"]
+        );
+        fs::remove_dir_all(workspace).unwrap();
+    }
+
+    #[test]
+    fn test_verify() {
+        // The skipped slot 13 is not reported: no block names it as its parent.
+        let workspace = workspace("verify", (10..=24).filter(|slot| *slot != 13), blockhash);
+        let reader = WorkspaceReader::open(&workspace).unwrap();
+        let (problems, lines) = output(|out| verify(&reader, out));
+        assert_eq!(problems, 0, "{lines:?}");
+        assert_eq!(lines, vec!["0 problem(s) found"]);
+
+        // A truncated cell, and a slot named as a parent but missing.
+        let path = cell_path(&workspace, 16, "blocks");
+        let data = fs::read(&path).unwrap();
+        fs::write(&path, &data[..data.len() - 1]).unwrap();
+        let slot_path = cell_path(&workspace, 21, "blocks");
+        fs::remove_dir_all(slot_path.parent().unwrap().parent().unwrap()).unwrap();
+        let (problems, lines) = output(|out| verify(&reader, out));
+        assert_eq!(problems, 2, "{lines:?}");
+        let range_path = |range| workspace.join("storage").join(range);
+        assert!(lines[0].starts_with(&format!(
+            "{}: slot 16 blocks: ",
+            range_path("range_000000000000000a_0000000000000014").display()
+        )));
+        assert_eq!(
+            lines[1],
+            format!(
+                "{}: missing slot 21",
+                range_path("range_0000000000000014_000000000000001e").display()
+            )
+        );
+        fs::remove_dir_all(workspace).unwrap();
+    }
+
+    #[test]
+    fn test_diff() {
+        let workspace_a = workspace("diff-a", 10..=24, blockhash);
+        let workspace_b = workspace("diff-b", 10..=24, blockhash);
+        let other_blockhash = |slot| blockhash(if slot == 24 { 0xff } else { slot });
+        let workspace_c = workspace("diff-c", 10..=24, other_blockhash);
+        let reader_a = WorkspaceReader::open(&workspace_a).unwrap();
+        let reader_b = WorkspaceReader::open(&workspace_b).unwrap();
+        let reader_c = WorkspaceReader::open(&workspace_c).unwrap();
+
+        let (differences, lines) = output(|out| diff(&reader_a, &reader_b, 0, Slot::MAX, out));
+        assert_eq!(differences, 0, "{lines:?}");
+        assert_eq!(lines, vec!["0 difference(s) found"]);
+
+        // Only the blocks cell of the last slot differs.
+        let (differences, lines) = output(|out| diff(&reader_a, &reader_c, 0, Slot::MAX, out));
+        assert_eq!(differences, 2, "{lines:?}");
+        assert_eq!(
+            lines[0],
+            format!(
+                "slot 24: blocks: blockhash {} vs {}",
+                blockhash(24),
+                blockhash(0xff)
+            )
+        );
+        assert!(lines[1].starts_with("slot 24: blocks: protobuf differs at byte "));
+        assert_eq!(lines[2], "2 difference(s) found");
+        let (differences, _) = output(|out| diff(&reader_a, &reader_c, 10, 23, out));
+        assert_eq!(differences, 0);
+
+        for workspace in [workspace_a, workspace_b, workspace_c] {
+            fs::remove_dir_all(workspace).unwrap();
+        }
+    }
+}
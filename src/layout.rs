@@ -0,0 +1,358 @@
+/// Layout parameters of a workspace, recorded in `layout.json` so that the range folders are
+/// never written with different parameters, and so that consumers know how to read them.
+use {
+    crate::{
+        storage::StorageManager,
+        templates::{KeyTemplates, Naming},
+    },
+    serde::{Deserialize, Serialize},
+    solana_sdk::{
+        clock::{Slot, DEFAULT_SLOTS_PER_EPOCH},
+        epoch_schedule::EpochSchedule,
+    },
+    std::{
+        fs,
+        io::{self, ErrorKind},
+        path::Path,
+    },
+};
+
+pub const LAYOUT_FILE_NAME: &str = "layout.json";
+
+/// How the slots are grouped into `range_<start>_<end>` folders.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum RangePolicy {
+    /// Ranges of `slot_range` slots, starting at the multiples of `slot_range`.
+    #[default]
+    Fixed,
+    /// Ranges of up to `slot_range` slots, starting at the first slot of each epoch, so that
+    /// no range spans two epochs.
+    Epoch {
+        #[serde(default = "default_slots_per_epoch")]
+        slots_per_epoch: u64,
+        /// Whether the first epochs are shorter, as on clusters created with warmup.
+        #[serde(default)]
+        warmup: bool,
+    },
+    /// Ranges starting at the first slot saved after the previous range, and closed once
+    /// their cells reach `target_bytes` or they hold `slot_range` slots.
+    Size { target_bytes: u64 },
+}
+
+fn default_slots_per_epoch() -> u64 {
+    DEFAULT_SLOTS_PER_EPOCH
+}
+
+impl RangePolicy {
+    /// The epoch schedule of the epoch policy.
+    pub fn epoch_schedule(&self) -> Option<EpochSchedule> {
+        match self {
+            Self::Epoch {
+                slots_per_epoch,
+                warmup,
+            } => Some(EpochSchedule::custom(
+                *slots_per_epoch,
+                *slots_per_epoch,
+                *warmup,
+            )),
+            _ => None,
+        }
+    }
+
+    /// The first slot and the end slot (exclusive) of the range containing `slot`, or `None`
+    /// for the size policy, whose ranges depend on the slots saved before.
+    pub fn range(&self, slot: Slot, slot_range: u64) -> Option<(Slot, Slot)> {
+        match self {
+            Self::Fixed => {
+                let start_slot = slot - (slot % slot_range);
+                Some((start_slot, start_slot + slot_range))
+            }
+            Self::Epoch { .. } => {
+                let epoch_schedule = self.epoch_schedule()?;
+                let epoch = epoch_schedule.get_epoch(slot);
+                let first_slot = epoch_schedule.get_first_slot_in_epoch(epoch);
+                let end_slot = epoch_schedule.get_last_slot_in_epoch(epoch) + 1;
+                let start_slot = slot - ((slot - first_slot) % slot_range);
+                Some((start_slot, end_slot.min(start_slot + slot_range)))
+            }
+            Self::Size { .. } => None,
+        }
+    }
+
+    /// The first slot of the first range starting at or after `slot`. Under the size policy,
+    /// ranges can start at any slot, so the range starts after `slot`, which may be partial.
+    pub fn next_range_start(&self, slot: Slot, slot_range: u64) -> Slot {
+        match self.range(slot, slot_range) {
+            Some((start_slot, _)) if start_slot == slot => slot,
+            Some((_, end_slot)) => end_slot,
+            None => slot + 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct WorkspaceLayout {
+    /// The number of slots in each range, or the maximum with the epoch and size policies.
+    pub slot_range: u64,
+    /// Workspaces written before the policy was recorded use the fixed policy.
+    #[serde(default)]
+    pub range_policy: RangePolicy,
+    /// Workspaces written before the templates were recorded use the default templates.
+    #[serde(default)]
+    pub templates: KeyTemplates,
+    /// Name of the cluster, for the `{cluster}` placeholder.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<String>,
+    /// Genesis hash of the cluster the workspace was initialised for. Workspaces written
+    /// before it was recorded, or without known genesis hash, accept any cluster.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genesis_hash: Option<String>,
+    /// Identity pubkey of the validator writing the workspace, for the `{identity}`
+    /// placeholder.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+}
+
+impl WorkspaceLayout {
+    /// Whether `start_slot` to `end_slot` (exclusive) is a range of this layout.
+    pub fn is_valid_range(&self, start_slot: Slot, end_slot: Slot) -> bool {
+        match self.range_policy.range(start_slot, self.slot_range) {
+            Some(range) => range == (start_slot, end_slot),
+            None => start_slot < end_slot && end_slot - start_slot <= self.slot_range,
+        }
+    }
+
+    /// The names of the folders and cells. The `{epoch}` placeholder follows the epoch
+    /// schedule of the epoch policy, or the schedule of the clusters without warmup. Errors
+    /// name the invalid field.
+    pub fn naming(&self) -> Result<Naming, (String, String)> {
+        Naming::new(
+            &self.templates,
+            self.cluster.as_deref(),
+            self.identity.as_deref(),
+            self.range_policy
+                .epoch_schedule()
+                .unwrap_or_else(EpochSchedule::without_warmup),
+        )
+    }
+
+    /// Reads the layout of a workspace, or `None` if it was not recorded.
+    pub fn load(workspace: &Path) -> io::Result<Option<Self>> {
+        let path = workspace.join(LAYOUT_FILE_NAME);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_slice(&fs::read(path)?)?))
+    }
+
+    /// Records the layout of a workspace, replacing the previous one atomically.
+    pub fn save(&self, workspace: &Path) -> io::Result<()> {
+        let path = workspace.join(LAYOUT_FILE_NAME);
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(tmp_path, path)
+    }
+
+    /// Checks that the workspace was written with this layout, and records it if the
+    /// workspace has none yet. A workspace without layout is accepted if its committed
+    /// ranges have the same size.
+    ///
+    /// A new workspace is only initialised for a known cluster, i.e. with a genesis hash. A
+    /// workspace initialised for a cluster only accepts the same genesis hash, and the cluster
+    /// names must match when both are known. A workspace written without genesis hash is
+    /// initialised for the first one given. The identity of the validator may change, unless
+    /// the templates use it.
+    pub fn check_or_record(&self, workspace: &Path) -> io::Result<()> {
+        let recorded = match Self::load(workspace)? {
+            Some(layout) => Some(layout),
+            None => Self::detect(workspace)?,
+        };
+        let mut layout = self.clone();
+        match &recorded {
+            None if self.genesis_hash.is_none() => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "the cluster of the new workspace {} is unknown: set genesis_hash or \
+                         ledger_path",
+                        workspace.display()
+                    ),
+                ));
+            }
+            Some(recorded) if recorded.genesis_hash.is_none() && self.genesis_hash.is_none() => {
+                log::error!(
+                    "COS: workspace {} was written without genesis hash and accepts the slots \
+                     of any cluster: set genesis_hash or ledger_path to record its cluster",
+                    workspace.display()
+                );
+            }
+            _ => {}
+        }
+        if let Some(recorded) = recorded {
+            self.check_cluster(workspace, &recorded)?;
+            let uses_identity = self.naming().is_ok_and(|naming| naming.uses_identity());
+            if recorded.templates != self.templates
+                || (uses_identity && recorded.identity != self.identity)
+            {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "workspace {} was written with the templates {:?} of identity {:?}, not \
+                         {:?} of identity {:?}: use another workspace",
+                        workspace.display(),
+                        recorded.templates,
+                        recorded.identity,
+                        self.templates,
+                        self.identity
+                    ),
+                ));
+            }
+            if recorded.slot_range != self.slot_range || recorded.range_policy != self.range_policy
+            {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "workspace {} was written with slot_range {} and range_policy {:?}, not \
+                         {} and {:?}: use cos-rechunk to change the ranges of the workspace, or \
+                         use another workspace",
+                        workspace.display(),
+                        recorded.slot_range,
+                        recorded.range_policy,
+                        self.slot_range,
+                        self.range_policy
+                    ),
+                ));
+            }
+            // The cluster name is kept when it is only known from the workspace.
+            layout.cluster = layout.cluster.or(recorded.cluster);
+        }
+        layout.save(workspace)
+    }
+
+    /// Checks that this layout is for the cluster the workspace was initialised for.
+    fn check_cluster(&self, workspace: &Path, recorded: &Self) -> io::Result<()> {
+        let genesis_hash_mismatch = match (&recorded.genesis_hash, &self.genesis_hash) {
+            (Some(recorded), Some(genesis_hash)) => recorded != genesis_hash,
+            (Some(_), None) => true,
+            // Checked against the cluster name only, and recorded from now on.
+            (None, _) => false,
+        };
+        let cluster_mismatch = matches!(
+            (&recorded.cluster, &self.cluster),
+            (Some(recorded), Some(cluster)) if recorded != cluster
+        );
+        if !genesis_hash_mismatch && !cluster_mismatch {
+            return Ok(());
+        }
+        Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "workspace {} was initialised for {}, not {}: the slots of different clusters \
+                 cannot share a workspace. Set genesis_hash or ledger_path for the cluster of \
+                 the workspace, or use another workspace",
+                workspace.display(),
+                recorded.describe_cluster(),
+                self.describe_cluster()
+            ),
+        ))
+    }
+
+    fn describe_cluster(&self) -> String {
+        match (&self.cluster, &self.genesis_hash) {
+            (Some(cluster), Some(genesis_hash)) => {
+                format!("cluster {cluster} (genesis hash {genesis_hash})")
+            }
+            (Some(cluster), None) => format!("cluster {cluster}"),
+            (None, Some(genesis_hash)) => format!("the cluster of genesis hash {genesis_hash}"),
+            (None, None) => "an unknown cluster".to_string(),
+        }
+    }
+
+    /// Layout of the committed ranges of a workspace without `layout.json`, or `None` if it
+    /// has no committed range. Such workspaces were written with the default templates, for
+    /// an unknown cluster.
+    fn detect(workspace: &Path) -> io::Result<Option<Self>> {
+        let storage_path = workspace.join("storage");
+        if !storage_path.is_dir() {
+            return Ok(None);
+        }
+        for entry in fs::read_dir(storage_path)? {
+            let entry = entry?;
+            if let Some((start, end)) = entry
+                .file_name()
+                .to_str()
+                .and_then(StorageManager::parse_slot_range)
+            {
+                return Ok(Some(Self {
+                    slot_range: end - start,
+                    range_policy: RangePolicy::Fixed,
+                    templates: KeyTemplates::default(),
+                    cluster: None,
+                    genesis_hash: None,
+                    identity: None,
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_sdk::hash::Hash};
+
+    #[test]
+    fn test_workspaces_are_initialised_for_a_cluster() {
+        let workspace = std::env::temp_dir().join(format!("cos-layout-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&workspace);
+        fs::create_dir_all(&workspace).unwrap();
+        let layout = WorkspaceLayout {
+            slot_range: 10,
+            ..Default::default()
+        };
+        let genesis_hash = Some(Hash::new_unique().to_string());
+        let with_genesis_hash = |genesis_hash: &Option<String>| WorkspaceLayout {
+            genesis_hash: genesis_hash.clone(),
+            ..layout.clone()
+        };
+
+        // A new workspace needs a genesis hash.
+        let err = layout.check_or_record(&workspace).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "the cluster of the new workspace {} is unknown: set genesis_hash or ledger_path",
+                workspace.display()
+            )
+        );
+        assert!(WorkspaceLayout::load(&workspace).unwrap().is_none());
+
+        // A workspace written without genesis hash is initialised for the first one given.
+        layout.save(&workspace).unwrap();
+        layout.check_or_record(&workspace).unwrap();
+        with_genesis_hash(&genesis_hash)
+            .check_or_record(&workspace)
+            .unwrap();
+        let err = layout.check_or_record(&workspace).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "workspace {} was initialised for the cluster of genesis hash {}, not an unknown \
+                 cluster: the slots of different clusters cannot share a workspace. Set \
+                 genesis_hash or ledger_path for the cluster of the workspace, or use another \
+                 workspace",
+                workspace.display(),
+                genesis_hash.as_ref().unwrap()
+            )
+        );
+        assert!(with_genesis_hash(&Some(Hash::new_unique().to_string()))
+            .check_or_record(&workspace)
+            .is_err());
+        with_genesis_hash(&genesis_hash)
+            .check_or_record(&workspace)
+            .unwrap();
+        fs::remove_dir_all(workspace).unwrap();
+    }
+}
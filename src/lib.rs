@@ -1,12 +1,80 @@
+//! Solana Geyser plugin archiving rooted slots to a local workspace, in the format of the
+//! Solana BigTable, for upload to Tencent Cloud Object Storage.
+//!
+//! Besides the plugin itself, the crate is a library for services that produce or consume
+//! the same workspace:
+//!
+//! - [`cos_types`] and [`conversions`]: the events assembled from the plugin notifications,
+//!   and their conversions to and from the Solana types.
+//! - [`storage`]: the [`storage::StorageManager`] saving slots to the workspace, and the
+//!   names of its folders and cell keys, [`layout`]: the range size recorded in the
+//!   workspace, [`templates`]: the configurable names of the folders and cells, and
+//!   [`cluster`]: the cluster the workspace was initialised for.
+//! - [`compression`], [`accounts`] and [`votes`]: the encoding of the cells.
+//! - [`reader`]: read access to a workspace.
+//!
+//! The public API follows semantic versioning: until 1.0, breaking changes bump the minor
+//! version and additions bump the patch version. The workspace layout and the cell encoding
+//! are part of the API. Structs that may gain fields are `#[non_exhaustive]`: they are built
+//! by the crate, e.g. from the configuration file, and read by their users. The modules
+//! backing the command line tools, such as `inspect`, are hidden and not part of the API.
+
+/// Account updates archived in the `accounts` table, and their filter.
+pub mod accounts;
+/// Rebuilds slot ranges from other sources than the plugin notifications.
+pub mod backfill;
+/// Backfill from a local validator ledger.
+#[cfg(feature = "ledger")]
+pub mod backfill_ledger;
+/// Backfill from an RPC node.
+pub mod backfill_rpc;
+/// The cluster a workspace archives, from its genesis hash.
+pub mod cluster;
+/// Compression of the cells, with a header naming the compression method.
+pub mod compression;
+/// Conversions from the plugin notifications to the event types, and from the event types to
+/// the Solana types they are archived as.
+pub mod conversions;
+/// Events assembled from the plugin notifications.
+pub mod cos_types;
+/// Errors of the plugin.
+pub mod errors;
+/// Transaction filter and transaction counts.
+pub mod filters;
+/// The Geyser plugin.
 pub mod geyser_plugin_cos;
+/// Configuration of the plugin.
 pub mod geyser_plugin_cos_config;
+/// Commands of `cos-inspect`.
+#[doc(hidden)]
+pub mod inspect;
+/// The `layout.json` file of the workspace.
+pub mod layout;
+/// Read access to a workspace.
+pub mod reader;
+/// Changes the range size of the committed ranges of a workspace.
+pub mod rechunk;
+/// Startup account snapshots.
+pub mod snapshot;
+/// The `status.json` file of the workspace.
+pub mod status;
+/// Workspace layout and the saving of slots.
+pub mod storage;
+/// The templates of the folder and cell names of a workspace.
+pub mod templates;
+/// The `timings.jsonl` file of each slot range.
+pub mod timings;
+/// Vote transactions archived in the `votes` table.
+pub mod votes;
 
-mod compression;
-mod conversions;
-mod cos_types;
+mod admin;
 mod datastore;
-mod errors;
-mod storage;
+mod http;
+mod lock;
+mod metrics;
+mod periodic;
+mod trace;
+mod watchdog;
 
 #[macro_use]
 extern crate serde_derive;
@@ -0,0 +1,122 @@
+/// Exclusive lock on a workspace, so that two processes never write to the same staging and
+/// storage folders.
+use {
+    serde::{Deserialize, Serialize},
+    std::{
+        fs::{self, File, OpenOptions},
+        io::{self, ErrorKind, Read, Seek, Write},
+        os::fd::AsRawFd,
+        path::{Path, PathBuf},
+    },
+};
+
+pub(crate) const LOCK_FILE_NAME: &str = "workspace.lock";
+
+/// Content of the lock file, only used to report who holds the lock.
+#[derive(Debug, Serialize, Deserialize)]
+struct LockOwner {
+    pid: u32,
+    hostname: String,
+    /// Time the lock was taken.
+    since: String,
+}
+
+/// Advisory lock on a workspace, held as a `flock` on its `workspace.lock` file until the lock
+/// is dropped. The kernel releases it when the process exits, even if it crashed, so a lock is
+/// never left behind. The file names the PID and hostname of the owner, for diagnostics.
+#[derive(Debug)]
+pub(crate) struct WorkspaceLock {
+    path: PathBuf,
+    file: File,
+}
+
+impl WorkspaceLock {
+    /// Takes the lock on `workspace`, creating the folder if needed. A lock held by another
+    /// process, or by another lock of this process, is an error.
+    pub(crate) fn acquire(workspace: &Path) -> io::Result<Self> {
+        fs::create_dir_all(workspace)?;
+        let path = workspace.join(LOCK_FILE_NAME);
+        // The file is never removed, so that every process locks the same file.
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        // SAFETY: the file descriptor is open for the duration of the call.
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() != ErrorKind::WouldBlock {
+                return Err(err);
+            }
+            let mut content = vec![];
+            file.read_to_end(&mut content)?;
+            let holder = match serde_json::from_slice::<LockOwner>(&content) {
+                Ok(owner) => format!(
+                    "process {} on host {} since {}",
+                    owner.pid, owner.hostname, owner.since
+                ),
+                // The owner is still writing the file.
+                Err(_) => "another process".to_string(),
+            };
+            return Err(io::Error::new(
+                ErrorKind::AlreadyExists,
+                format!(
+                    "workspace {} is locked by {holder}: another plugin or tool is using it",
+                    workspace.display(),
+                ),
+            ));
+        }
+
+        let owner = LockOwner {
+            pid: std::process::id(),
+            hostname: hostname(),
+            since: chrono::Utc::now().to_rfc3339(),
+        };
+        file.set_len(0)?;
+        file.rewind()?;
+        file.write_all(&serde_json::to_vec(&owner)?)?;
+        Ok(Self { path, file })
+    }
+}
+
+impl Drop for WorkspaceLock {
+    /// Clears the owner from the file. The lock itself is released when the file is closed.
+    fn drop(&mut self) {
+        if let Err(err) = self.file.set_len(0) {
+            log::error!("COS: Failed to clear {}: {err}", self.path.display());
+        }
+    }
+}
+
+fn hostname() -> String {
+    let mut buffer = [0u8; 256];
+    // SAFETY: the buffer is valid for its length, and the name is truncated to fit it.
+    if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } != 0 {
+        return String::new();
+    }
+    let len = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..len]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_is_exclusive_until_dropped() {
+        let workspace = std::env::temp_dir().join(format!("cos-lock-{}", std::process::id()));
+        let lock = WorkspaceLock::acquire(&workspace).unwrap();
+        let err = WorkspaceLock::acquire(&workspace).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        assert!(err
+            .to_string()
+            .contains(&format!("process {}", std::process::id())));
+
+        drop(lock);
+        // A lock file left behind, e.g. by a crashed process, does not block the next owner.
+        assert!(workspace.join(LOCK_FILE_NAME).exists());
+        WorkspaceLock::acquire(&workspace).unwrap();
+        fs::remove_dir_all(workspace).unwrap();
+    }
+}
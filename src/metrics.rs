@@ -0,0 +1,367 @@
+/// Prometheus metrics of the plugin, served by an optional embedded HTTP server.
+use {
+    crate::{
+        compression::CompressionMethod,
+        errors::GeyserPluginCosError,
+        http::{HttpResponse, HttpServer},
+        status,
+        timings::SlotTimings,
+        watchdog::EventKind,
+    },
+    prometheus::{
+        Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+        IntGaugeVec, Opts, Registry, TextEncoder,
+    },
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_sdk::clock::Slot,
+    std::{io, net::SocketAddr, sync::OnceLock, time::Duration},
+};
+
+pub(crate) struct Metrics {
+    registry: Registry,
+    pub slots_saved: IntCounter,
+    pub slots_discarded: IntCounter,
+    pub cache_size: IntGauge,
+    pub save_seconds: Histogram,
+    compressed_bytes: IntCounterVec,
+    uncompressed_bytes: IntCounterVec,
+    compression_ratio: prometheus::GaugeVec,
+    pub bytes_written: IntCounter,
+    pub ranges_committed: IntCounter,
+    latest_rooted_slot: IntGauge,
+    last_committed_slot: IntGauge,
+    commit_lag_slots: IntGauge,
+    errors: IntCounterVec,
+    event_age_seconds: IntGaugeVec,
+    event_stalled: IntGaugeVec,
+    event_stalls: IntCounterVec,
+    slot_stage_seconds: HistogramVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("cos".to_string()), None).unwrap();
+        let metrics = Self {
+            slots_saved: IntCounter::new("slots_saved_total", "Rooted slots saved to staging")
+                .unwrap(),
+            slots_discarded: IntCounter::new(
+                "slots_discarded_total",
+                "Slots discarded because they were not rooted",
+            )
+            .unwrap(),
+            cache_size: IntGauge::new("cache_slots", "Slots held in the in-memory cache").unwrap(),
+            save_seconds: Histogram::with_opts(
+                HistogramOpts::new("slot_save_seconds", "Time to save a slot to storage")
+                    .buckets(prometheus::exponential_buckets(0.001, 2.0, 14).unwrap()),
+            )
+            .unwrap(),
+            compressed_bytes: IntCounterVec::new(
+                Opts::new("cell_compressed_bytes_total", "Size of the cells written"),
+                &["method"],
+            )
+            .unwrap(),
+            uncompressed_bytes: IntCounterVec::new(
+                Opts::new(
+                    "cell_uncompressed_bytes_total",
+                    "Size of the cells written, before compression",
+                ),
+                &["method"],
+            )
+            .unwrap(),
+            compression_ratio: prometheus::GaugeVec::new(
+                Opts::new(
+                    "compression_ratio",
+                    "Uncompressed over compressed size of the cells written",
+                ),
+                &["method"],
+            )
+            .unwrap(),
+            bytes_written: IntCounter::new("bytes_written_total", "Bytes written to the workspace")
+                .unwrap(),
+            ranges_committed: IntCounter::new(
+                "ranges_committed_total",
+                "Slot ranges moved from staging to storage",
+            )
+            .unwrap(),
+            latest_rooted_slot: IntGauge::new("latest_rooted_slot", "Latest rooted slot").unwrap(),
+            last_committed_slot: IntGauge::new(
+                "last_committed_slot",
+                "Last slot of the latest slot range committed to storage",
+            )
+            .unwrap(),
+            commit_lag_slots: IntGauge::new(
+                "commit_lag_slots",
+                "Slots between the latest rooted slot and the last committed range",
+            )
+            .unwrap(),
+            errors: IntCounterVec::new(
+                Opts::new("errors_total", "Errors returned to the validator, by kind"),
+                &["error"],
+            )
+            .unwrap(),
+            event_age_seconds: IntGaugeVec::new(
+                Opts::new(
+                    "event_age_seconds",
+                    "Seconds since the last notification, by kind",
+                ),
+                &["event"],
+            )
+            .unwrap(),
+            event_stalled: IntGaugeVec::new(
+                Opts::new(
+                    "event_stalled",
+                    "1 while a notification stream is stalled, by kind",
+                ),
+                &["event"],
+            )
+            .unwrap(),
+            event_stalls: IntCounterVec::new(
+                Opts::new(
+                    "event_stalls_total",
+                    "Times a notification stream stalled, by kind",
+                ),
+                &["event"],
+            )
+            .unwrap(),
+            slot_stage_seconds: HistogramVec::new(
+                HistogramOpts::new(
+                    "slot_stage_seconds",
+                    "Time from the processed status of a slot to each stage",
+                )
+                .buckets(prometheus::exponential_buckets(0.1, 2.0, 15).unwrap()),
+                &["stage"],
+            )
+            .unwrap(),
+            registry,
+        };
+        metrics.register();
+        metrics
+    }
+
+    fn register(&self) {
+        let collectors: Vec<Box<dyn prometheus::core::Collector>> = vec![
+            Box::new(self.slots_saved.clone()),
+            Box::new(self.slots_discarded.clone()),
+            Box::new(self.cache_size.clone()),
+            Box::new(self.save_seconds.clone()),
+            Box::new(self.compressed_bytes.clone()),
+            Box::new(self.uncompressed_bytes.clone()),
+            Box::new(self.compression_ratio.clone()),
+            Box::new(self.bytes_written.clone()),
+            Box::new(self.ranges_committed.clone()),
+            Box::new(self.latest_rooted_slot.clone()),
+            Box::new(self.last_committed_slot.clone()),
+            Box::new(self.commit_lag_slots.clone()),
+            Box::new(self.errors.clone()),
+            Box::new(self.event_age_seconds.clone()),
+            Box::new(self.event_stalled.clone()),
+            Box::new(self.event_stalls.clone()),
+            Box::new(self.slot_stage_seconds.clone()),
+        ];
+        for collector in collectors {
+            self.registry.register(collector).unwrap();
+        }
+    }
+
+    pub fn record_cell(&self, method: CompressionMethod, compressed: usize, uncompressed: usize) {
+        let method = format!("{method:?}");
+        let compressed_bytes = self.compressed_bytes.with_label_values(&[&method]);
+        let uncompressed_bytes = self.uncompressed_bytes.with_label_values(&[&method]);
+        compressed_bytes.inc_by(compressed as u64);
+        uncompressed_bytes.inc_by(uncompressed as u64);
+        if compressed_bytes.get() > 0 {
+            self.compression_ratio
+                .with_label_values(&[&method])
+                .set(uncompressed_bytes.get() as f64 / compressed_bytes.get() as f64);
+        }
+    }
+
+    pub fn record_rooted(&self, slot: Slot) {
+        self.latest_rooted_slot
+            .set(self.latest_rooted_slot.get().max(slot as i64));
+        self.update_commit_lag();
+    }
+
+    /// Records a committed range, ending at `end_slot` (exclusive).
+    pub fn record_committed(&self, end_slot: Slot) {
+        self.ranges_committed.inc();
+        self.last_committed_slot.set(
+            self.last_committed_slot
+                .get()
+                .max(end_slot.saturating_sub(1) as i64),
+        );
+        self.update_commit_lag();
+    }
+
+    fn update_commit_lag(&self) {
+        if self.last_committed_slot.get() > 0 {
+            // A range committed early through the admin API ends after the latest rooted slot.
+            self.commit_lag_slots
+                .set((self.latest_rooted_slot.get() - self.last_committed_slot.get()).max(0));
+        }
+    }
+
+    pub fn record_error(&self, err: &GeyserPluginError) {
+        let kind = match err {
+            GeyserPluginError::Custom(err) => match err.downcast_ref::<GeyserPluginCosError>() {
+                Some(err) => err.name(),
+                None => "Custom",
+            },
+            // Every `io::Error` converts to this variant, not only config file errors.
+            GeyserPluginError::ConfigFileOpenError(_) => "Io",
+            GeyserPluginError::ConfigFileReadError { .. } => "ConfigFileReadError",
+            GeyserPluginError::AccountsUpdateError { .. } => "AccountsUpdateError",
+            GeyserPluginError::SlotStatusUpdateError { .. } => "SlotStatusUpdateError",
+            GeyserPluginError::TransactionUpdateError { .. } => "TransactionUpdateError",
+        };
+        self.errors.with_label_values(&[kind]).inc();
+    }
+
+    pub fn record_event_age(&self, kind: EventKind, age: Duration) {
+        self.event_age_seconds
+            .with_label_values(&[kind.name()])
+            .set(age.as_secs() as i64);
+    }
+
+    pub fn record_stalled(&self, kind: EventKind, stalled: bool) {
+        self.event_stalled
+            .with_label_values(&[kind.name()])
+            .set(stalled as i64);
+        if stalled {
+            self.event_stalls.with_label_values(&[kind.name()]).inc();
+        }
+    }
+
+    /// Records the time from the processed status of a slot to `stage`, if both are known.
+    pub fn record_stage(&self, stage: &str, timings: &SlotTimings, time: Option<u64>) {
+        if let Some(millis) = timings.since_processed(time) {
+            self.slot_stage_seconds
+                .with_label_values(&[stage])
+                .observe(millis as f64 / 1000.0);
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+        buffer
+    }
+}
+
+/// The metrics of the plugin, shared by all its components.
+pub(crate) fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// Counts the error of a failed notification before it is returned to the validator.
+pub(crate) fn count_error<T>(result: Result<T, GeyserPluginError>) -> Result<T, GeyserPluginError> {
+    result.map_err(|err| {
+        metrics().record_error(&err);
+        status::record_error(&err);
+        err
+    })
+}
+
+/// Embedded HTTP server answering `GET /metrics`. Stopped when dropped.
+pub struct MetricsServer {
+    _server: HttpServer,
+}
+
+impl MetricsServer {
+    pub fn start(address: SocketAddr) -> io::Result<Self> {
+        let server = HttpServer::start("cosMetrics", address, |request| {
+            match (request.method.as_str(), request.path.as_str()) {
+                ("GET", "/metrics") => HttpResponse {
+                    status: "200 OK",
+                    content_type: "text/plain; version=0.0.4",
+                    body: metrics().encode(),
+                },
+                _ => HttpResponse::not_found(),
+            }
+        })?;
+        log::info!(
+            "COS: Serving metrics on http://{}/metrics",
+            server.local_address()
+        );
+        Ok(Self { _server: server })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            datastore::Datastore, geyser_plugin_cos::save_slots,
+            geyser_plugin_cos_config::GeyserPluginCosConfig, storage::StorageManager,
+        },
+        serde_json::json,
+        solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+        solana_sdk::hash::Hash,
+        std::{
+            collections::BTreeMap,
+            io::{Read, Write},
+            net::TcpStream,
+            sync::Mutex,
+        },
+    };
+
+    /// The samples of the `/metrics` page served at `address`, by name and labels.
+    fn scrape(address: SocketAddr) -> BTreeMap<String, f64> {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET /metrics HTTP/1.1\r\nHost: {address}\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK"), "{head}");
+        body.lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| {
+                let (name, value) = line.rsplit_once(' ').unwrap();
+                (name.to_string(), value.parse().unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_metrics_after_a_save() {
+        let workspace = std::env::temp_dir().join(format!("cos-metrics-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&workspace);
+        let config: GeyserPluginCosConfig = serde_json::from_value(json!({
+            "workspace": workspace,
+            "genesis_hash": Hash::default().to_string(),
+            "slot_range": 10,
+            "commit_slot_delay": 5,
+            "compression": "Zstd",
+        }))
+        .unwrap();
+        let datastore = Mutex::new(Datastore::new(&config));
+        let storage = StorageManager::new(&config).unwrap();
+        {
+            let mut datastore = datastore.lock().unwrap();
+            let block_with_entries = datastore.get_mut_entry(12);
+            block_with_entries.block.blockhash = Hash::new_unique().to_string();
+            block_with_entries.slot_status = SlotStatus::Rooted;
+            // Not rooted, so discarded.
+            datastore.get_mut_entry(13);
+        }
+
+        let server = MetricsServer::start("127.0.0.1:0".parse().unwrap()).unwrap();
+        let before = scrape(server._server.local_address());
+        save_slots(&datastore, &storage, [12, 13]).unwrap();
+        let after = scrape(server._server.local_address());
+
+        // Other tests save slots as well, so the counters only increase by at least as much.
+        let increase = |name: &str| after[name] - before.get(name).copied().unwrap_or_default();
+        assert!(increase("cos_slots_saved_total") >= 1.0);
+        assert!(increase("cos_slots_discarded_total") >= 1.0);
+        assert!(increase("cos_slot_save_seconds_count") >= 1.0);
+        assert!(increase("cos_bytes_written_total") > 0.0);
+        assert!(increase(r#"cos_cell_compressed_bytes_total{method="Zstd"}"#) > 0.0);
+        assert!(after[r#"cos_compression_ratio{method="Zstd"}"#] > 0.0);
+        std::fs::remove_dir_all(workspace).unwrap();
+    }
+}
@@ -0,0 +1,45 @@
+/// Background thread running a task at a fixed interval.
+use std::{
+    io,
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Runs a task every interval, and once more when dropped.
+pub(crate) struct PeriodicTask {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PeriodicTask {
+    pub fn start<F>(name: &str, interval: Duration, mut task: F) -> io::Result<Self>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || loop {
+                task();
+                if let Err(RecvTimeoutError::Disconnected) = stopped.recv_timeout(interval) {
+                    task();
+                    break;
+                }
+            })?;
+        Ok(Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for PeriodicTask {
+    fn drop(&mut self) {
+        // Disconnects the channel, which wakes up the thread.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
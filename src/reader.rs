@@ -0,0 +1,448 @@
+/// Read access to the slot ranges written by the plugin.
+use {
+    crate::{
+        accounts::AccountUpdates,
+        compression::decompress,
+        filters::TransactionCounts,
+        layout::WorkspaceLayout,
+        snapshot::SnapshotManifest,
+        templates::Naming,
+        timings::{SlotTimings, TIMINGS_FILE_NAME},
+        votes::{merge_votes, VoteTransactions},
+    },
+    prost::Message,
+    solana_sdk::clock::Slot,
+    solana_storage_proto::convert::{entries, generated},
+    solana_transaction_status::{ConfirmedBlock, EntrySummary},
+    std::{
+        collections::BTreeMap,
+        fs,
+        io::{self, ErrorKind},
+        path::{Path, PathBuf},
+        sync::Arc,
+    },
+};
+
+/// Folder a slot range was found in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RangeLocation {
+    /// Committed ranges, ready to be uploaded.
+    Storage,
+    /// Ranges still being written.
+    Staging,
+    /// Ranges from a folder that is not a plugin workspace (e.g. a reference export).
+    Export,
+}
+
+impl std::fmt::Display for RangeLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RangeLocation::Storage => write!(f, "storage"),
+            RangeLocation::Staging => write!(f, "staging"),
+            RangeLocation::Export => write!(f, "export"),
+        }
+    }
+}
+
+/// A range folder, named by the `range_folder` template.
+#[derive(Clone, Debug)]
+pub struct SlotRange {
+    /// First slot of the range.
+    pub start: Slot,
+    /// End slot of the range (exclusive).
+    pub end: Slot,
+    pub location: RangeLocation,
+    pub path: PathBuf,
+}
+
+impl SlotRange {
+    pub fn contains(&self, slot: Slot) -> bool {
+        self.start <= slot && slot < self.end
+    }
+
+    /// Times recorded for the slots of the range, if any.
+    pub fn timings(&self) -> io::Result<Option<Vec<SlotTimings>>> {
+        let path = self.path.join(TIMINGS_FILE_NAME);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(SlotTimings::load(&path)?))
+    }
+}
+
+/// A slot folder inside a slot range, named by the `slot_folder` template.
+#[derive(Clone, Debug)]
+pub struct SlotCells {
+    pub slot: Slot,
+    pub path: PathBuf,
+    naming: Arc<Naming>,
+}
+
+impl SlotCells {
+    /// Path of the cell stored for `table`, if any.
+    pub fn cell_path(&self, table: &str) -> Option<PathBuf> {
+        self.naming.list_cells(&self.path).ok()?.remove(table)
+    }
+
+    /// Content hashes recorded in canonical mode, keyed by `<table>/<key>`.
+    pub fn content_hashes(&self) -> io::Result<Option<BTreeMap<String, String>>> {
+        let path = self.path.join("content_hashes.json");
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content_hashes = serde_json::from_slice(&fs::read(path)?)?;
+        Ok(Some(content_hashes))
+    }
+
+    /// Transaction counts recorded when a transaction filter is configured.
+    pub fn transaction_counts(&self) -> io::Result<Option<TransactionCounts>> {
+        let path = self.path.join("transaction_counts.json");
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_slice(&fs::read(path)?)?))
+    }
+
+    /// Names of the tables stored for this slot.
+    pub fn tables(&self) -> io::Result<Vec<String>> {
+        Ok(self.naming.list_cells(&self.path)?.into_keys().collect())
+    }
+}
+
+/// Reads slot ranges from a workspace, or from any folder holding range folders.
+pub struct WorkspaceReader {
+    roots: Vec<(RangeLocation, PathBuf)>,
+    /// Folder of the startup snapshots, for plugin workspaces.
+    snapshots_path: Option<PathBuf>,
+    /// Layout recorded in plugin workspaces, or next to the range folders of exports.
+    layout: Option<WorkspaceLayout>,
+    /// Names of the folders and cells, from the templates of the layout.
+    naming: Arc<Naming>,
+}
+
+impl WorkspaceReader {
+    /// Opens `path` as a plugin workspace if it has a `storage` folder,
+    /// otherwise as a folder containing range folders directly. The folders and cells are
+    /// named by the templates of the `layout.json` file of `path`, or by the default ones.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if !path.is_dir() {
+            return Err(io::Error::new(
+                ErrorKind::NotFound,
+                format!("{} is not a directory", path.display()),
+            ));
+        }
+        let storage_path = path.join("storage");
+        let (roots, snapshots_path) = if storage_path.is_dir() {
+            (
+                vec![
+                    (RangeLocation::Storage, storage_path),
+                    (RangeLocation::Staging, path.join("staging")),
+                ],
+                Some(path.join("snapshots")),
+            )
+        } else {
+            (vec![(RangeLocation::Export, path.to_path_buf())], None)
+        };
+        let layout = WorkspaceLayout::load(path)?;
+        let naming = match &layout {
+            Some(layout) => layout.naming().map_err(|(field, msg)| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid {field} in the layout of {}: {msg}", path.display()),
+                )
+            })?,
+            None => Naming::default(),
+        };
+        Ok(Self {
+            roots,
+            snapshots_path,
+            layout,
+            naming: Arc::new(naming),
+        })
+    }
+
+    /// Layout recorded in the workspace, `None` for exports without `layout.json` and for
+    /// workspaces written before it was recorded.
+    pub fn layout(&self) -> Option<&WorkspaceLayout> {
+        self.layout.as_ref()
+    }
+
+    /// All slot ranges, ordered by first slot.
+    pub fn ranges(&self) -> io::Result<Vec<SlotRange>> {
+        let mut ranges = vec![];
+        for (location, root) in &self.roots {
+            if !root.is_dir() {
+                continue;
+            }
+            for range in self.naming.list_ranges(root)? {
+                ranges.push(SlotRange {
+                    start: range.start,
+                    end: range.end,
+                    location: *location,
+                    path: root.join(range.name),
+                });
+            }
+        }
+        ranges.sort_by_key(|range| (range.start, range.end));
+        Ok(ranges)
+    }
+
+    /// All slots found in `range`, ordered by slot. Slots outside the range bounds are
+    /// returned as well.
+    pub fn slots(&self, range: &SlotRange) -> io::Result<Vec<SlotCells>> {
+        let mut slots = vec![];
+        for entry in fs::read_dir(&range.path)? {
+            let path = entry?.path();
+            let Some(folder_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if let Some(slot) = self.naming.parse_slot_folder(folder_name) {
+                slots.push(SlotCells {
+                    slot,
+                    path,
+                    naming: self.naming.clone(),
+                });
+            }
+        }
+        slots.sort_by_key(|slot| slot.slot);
+        Ok(slots)
+    }
+
+    /// All slots of all ranges. When a slot is found in several ranges, committed ranges
+    /// are preferred.
+    pub fn all_slots(&self) -> io::Result<BTreeMap<Slot, SlotCells>> {
+        let mut slots = BTreeMap::new();
+        let mut ranges = self.ranges()?;
+        ranges.sort_by_key(|range| range.location == RangeLocation::Staging);
+        for range in ranges {
+            for slot_cells in self.slots(&range)? {
+                slots.entry(slot_cells.slot).or_insert(slot_cells);
+            }
+        }
+        Ok(slots)
+    }
+
+    /// Finds the cells stored for `slot`, preferring committed ranges.
+    pub fn find_slot(&self, slot: Slot) -> io::Result<Option<SlotCells>> {
+        for range in self.ranges()?.iter().filter(|range| range.contains(slot)) {
+            let path = range.path.join(self.naming.slot_folder(slot));
+            if path.is_dir() {
+                return Ok(Some(SlotCells {
+                    slot,
+                    path,
+                    naming: self.naming.clone(),
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Folders of the complete startup snapshots, with their manifests, ordered by slot.
+    pub fn snapshots(&self) -> io::Result<Vec<(PathBuf, SnapshotManifest)>> {
+        let mut snapshots = vec![];
+        let Some(snapshots_path) = self.snapshots_path.as_ref().filter(|path| path.is_dir()) else {
+            return Ok(snapshots);
+        };
+        for entry in fs::read_dir(snapshots_path)? {
+            let path = entry?.path();
+            if path.extension().is_none() && path.join("manifest.json").is_file() {
+                let manifest = SnapshotManifest::load(&path)?;
+                snapshots.push((path, manifest));
+            }
+        }
+        snapshots.sort_by_key(|(_, manifest)| manifest.slot);
+        Ok(snapshots)
+    }
+
+    /// Reads and decompresses the cell stored for `table`.
+    pub fn read_cell(slot: &SlotCells, table: &str) -> io::Result<Option<Vec<u8>>> {
+        match slot.cell_path(table) {
+            Some(path) => Ok(Some(decompress(&fs::read(path)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the `blocks` cell of the full block. When vote transactions are stored in the
+    /// `votes` table, they are inserted back, giving the cell the plugin would have written
+    /// without `separate_votes`.
+    pub fn read_block_cell(slot: &SlotCells) -> io::Result<Option<Vec<u8>>> {
+        let Some(data) = Self::read_cell(slot, "blocks")? else {
+            return Ok(None);
+        };
+        let Some(votes) = Self::read_votes(slot)? else {
+            return Ok(Some(data));
+        };
+        let mut block = generated::ConfirmedBlock::decode(&data[..]).map_err(invalid_data)?;
+        merge_votes(&mut block, votes);
+        Ok(Some(block.encode_to_vec()))
+    }
+
+    /// Reads the full block, including the vote transactions stored in the `votes` table.
+    pub fn read_block(slot: &SlotCells) -> io::Result<Option<ConfirmedBlock>> {
+        let Some(data) = Self::read_block_cell(slot)? else {
+            return Ok(None);
+        };
+        let block = generated::ConfirmedBlock::decode(&data[..]).map_err(invalid_data)?;
+        Ok(Some(block.try_into().map_err(invalid_data)?))
+    }
+
+    /// Vote transactions stored apart from the `blocks` cell, with their position in the
+    /// full block.
+    pub fn read_votes(slot: &SlotCells) -> io::Result<Option<VoteTransactions>> {
+        let Some(data) = Self::read_cell(slot, "votes")? else {
+            return Ok(None);
+        };
+        Ok(Some(
+            VoteTransactions::decode(&data[..]).map_err(invalid_data)?,
+        ))
+    }
+
+    pub fn read_entries(slot: &SlotCells) -> io::Result<Option<Vec<EntrySummary>>> {
+        let Some(data) = Self::read_cell(slot, "entries")? else {
+            return Ok(None);
+        };
+        let entries = entries::Entries::decode(&data[..]).map_err(invalid_data)?;
+        Ok(Some(entries.entries.into_iter().map(Into::into).collect()))
+    }
+
+    /// Account updates of the slot, in write version order.
+    pub fn read_accounts(slot: &SlotCells) -> io::Result<Option<AccountUpdates>> {
+        let Some(data) = Self::read_cell(slot, "accounts")? else {
+            return Ok(None);
+        };
+        Ok(Some(
+            AccountUpdates::decode(&data[..]).map_err(invalid_data)?,
+        ))
+    }
+}
+
+fn invalid_data<E: std::fmt::Display>(err: E) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("{err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            cos_types::CosVersionedConfirmedBlockWithEntries,
+            geyser_plugin_cos_config::GeyserPluginCosConfig,
+            storage::{Storage, StorageManager},
+        },
+        serde_json::json,
+        solana_sdk::{
+            hash::Hash,
+            pubkey::Pubkey,
+            signature::Signature,
+            system_instruction,
+            transaction::Transaction,
+            vote::{instruction::vote, state::Vote},
+        },
+        solana_transaction_status::{TransactionStatusMeta, VersionedTransactionWithStatusMeta},
+    };
+
+    /// A block of two vote transactions between two transfers.
+    fn block_with_votes() -> CosVersionedConfirmedBlockWithEntries {
+        let payer = Pubkey::new_unique();
+        let mut block = CosVersionedConfirmedBlockWithEntries::default();
+        for index in 0..4 {
+            let instruction = if index % 2 == 0 {
+                system_instruction::transfer(&payer, &Pubkey::new_unique(), 1)
+            } else {
+                vote(
+                    &Pubkey::new_unique(),
+                    &payer,
+                    Vote::new(vec![index], Hash::default()),
+                )
+            };
+            let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer));
+            transaction.signatures = vec![Signature::new_unique()];
+            block
+                .block
+                .transactions
+                .push(VersionedTransactionWithStatusMeta {
+                    transaction: transaction.into(),
+                    meta: TransactionStatusMeta::default(),
+                });
+        }
+        block.vote_transaction_indexes = [1, 3].into();
+        block
+    }
+
+    /// Saves `block` at slot 12 of the workspace `name`, and returns the slot read back.
+    fn save_and_read(
+        name: &str,
+        separate_votes: bool,
+        block: &CosVersionedConfirmedBlockWithEntries,
+    ) -> (PathBuf, SlotCells) {
+        let workspace =
+            std::env::temp_dir().join(format!("cos-reader-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&workspace);
+        let config: GeyserPluginCosConfig = serde_json::from_value(json!({
+            "workspace": workspace,
+            "genesis_hash": Hash::default().to_string(),
+            "slot_range": 10,
+            "commit_slot_delay": 5,
+            "separate_votes": separate_votes,
+        }))
+        .unwrap();
+        let storage = StorageManager::new(&config).unwrap();
+        storage.save(12, block).unwrap();
+        storage.commit_all().unwrap();
+        drop(storage);
+        let slot = WorkspaceReader::open(&workspace)
+            .unwrap()
+            .all_slots()
+            .unwrap()
+            .remove(&12)
+            .unwrap();
+        (workspace, slot)
+    }
+
+    #[test]
+    fn test_votes_are_merged_back_into_the_block() {
+        let block = block_with_votes();
+        let (whole_workspace, whole) = save_and_read("whole", false, &block);
+        let (split_workspace, split) = save_and_read("split", true, &block);
+        assert!(whole.cell_path("votes").is_none());
+        assert_eq!(
+            WorkspaceReader::read_votes(&split)
+                .unwrap()
+                .unwrap()
+                .transactions
+                .len(),
+            2
+        );
+        let stored = WorkspaceReader::read_cell(&split, "blocks")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            generated::ConfirmedBlock::decode(&stored[..])
+                .unwrap()
+                .transactions
+                .len(),
+            2
+        );
+
+        assert_eq!(
+            WorkspaceReader::read_block_cell(&split).unwrap(),
+            WorkspaceReader::read_block_cell(&whole).unwrap()
+        );
+        let signatures: Vec<_> = WorkspaceReader::read_block(&split)
+            .unwrap()
+            .unwrap()
+            .transactions
+            .into_iter()
+            .map(|transaction| *transaction.transaction_signature())
+            .collect();
+        let expected: Vec<_> = block
+            .block
+            .transactions
+            .iter()
+            .map(|transaction| transaction.transaction.signatures[0])
+            .collect();
+        assert_eq!(signatures, expected);
+        fs::remove_dir_all(whole_workspace).unwrap();
+        fs::remove_dir_all(split_workspace).unwrap();
+    }
+}
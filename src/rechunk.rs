@@ -0,0 +1,357 @@
+/// Moves the slots of the committed ranges of a workspace into ranges of another size, and
+/// records the new size in `layout.json`.
+use {
+    crate::{
+        layout::{RangePolicy, WorkspaceLayout},
+        lock::WorkspaceLock,
+        storage::StorageManager,
+        timings::{SlotTimings, TIMINGS_FILE_NAME},
+    },
+    solana_sdk::clock::Slot,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fs,
+        io::{self, ErrorKind},
+        path::Path,
+    },
+};
+
+/// Folder of the workspace holding the new ranges until every slot was moved.
+pub const RECHUNK_FOLDER_NAME: &str = "rechunk.partial";
+
+#[derive(Debug, Default)]
+pub struct RechunkSummary {
+    /// Number of slots moved.
+    pub slots: u64,
+    /// Number of ranges before the change.
+    pub old_ranges: u64,
+    /// Number of ranges after the change.
+    pub new_ranges: u64,
+}
+
+/// Re-chunks the `storage` folder of `workspace` into the ranges of `layout`. The size
+/// policy is not supported, as its ranges depend on the size of the slots saved before, nor
+/// are range folders named after their block time, which is only known when slots are saved.
+///
+/// Slot folders are renamed, never copied, into the new ranges built in the `rechunk.partial`
+/// folder, and the new ranges are moved to storage once every old range is empty. An
+/// interrupted run is completed by running it again with the same layout.
+///
+/// The workspace is locked while it runs, so it fails if a plugin or a backfill uses it, and
+/// it must have no slots in staging, which are in the ranges of the previous layout. The
+/// templates, the cluster and the identity of `layout` must be the ones recorded in the
+/// workspace.
+pub fn rechunk(workspace: &Path, layout: &WorkspaceLayout) -> io::Result<RechunkSummary> {
+    if layout.slot_range == 0 {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "slot_range must be greater than 0",
+        ));
+    }
+    if let RangePolicy::Size { .. } = layout.range_policy {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "ranges cannot be re-chunked with the size policy",
+        ));
+    }
+    let naming = layout.naming().map_err(|(field, msg)| {
+        io::Error::new(ErrorKind::InvalidInput, format!("invalid {field}: {msg}"))
+    })?;
+    if naming.range_uses_block_time() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "ranges named after their block time cannot be re-chunked",
+        ));
+    }
+    let format_range = |slot| {
+        let (start_slot, end_slot) = layout
+            .range_policy
+            .range(slot, layout.slot_range)
+            .unwrap_or_default();
+        naming.range_folder(start_slot, Some(end_slot), None)
+    };
+    let _workspace_lock = WorkspaceLock::acquire(workspace)?;
+    StorageManager::check_staging_is_empty(workspace)?;
+    if let Some(recorded) = WorkspaceLayout::load(workspace)? {
+        if (
+            &recorded.templates,
+            &recorded.cluster,
+            &recorded.genesis_hash,
+            &recorded.identity,
+        ) != (
+            &layout.templates,
+            &layout.cluster,
+            &layout.genesis_hash,
+            &layout.identity,
+        ) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "the templates, the cluster and the identity of a workspace cannot be changed",
+            ));
+        }
+    }
+    let storage_path = workspace.join("storage");
+    let partial_path = workspace.join(RECHUNK_FOLDER_NAME);
+    fs::create_dir_all(&storage_path)?;
+    fs::create_dir_all(&partial_path)?;
+
+    let mut summary = RechunkSummary::default();
+    for old_range in naming.list_ranges(&storage_path)? {
+        let old_path = storage_path.join(&old_range.name);
+        let old_range = old_range.name;
+        summary.old_ranges += 1;
+        let mut timings = BTreeMap::<Slot, SlotTimings>::new();
+        let timings_path = old_path.join(TIMINGS_FILE_NAME);
+        if timings_path.is_file() {
+            for slot_timings in SlotTimings::load(&timings_path)? {
+                timings.insert(slot_timings.slot, slot_timings);
+            }
+        }
+
+        let mut new_ranges = BTreeSet::new();
+        for entry in fs::read_dir(&old_path)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let Some(slot) = file_name
+                .to_str()
+                .and_then(|name| naming.parse_slot_folder(name))
+            else {
+                continue;
+            };
+            let new_range = format_range(slot);
+            let new_range_path = partial_path.join(&new_range);
+            let new_slot_path = new_range_path.join(&file_name);
+            if new_slot_path.exists() {
+                return Err(io::Error::new(
+                    ErrorKind::AlreadyExists,
+                    format!("slot {slot} of {old_range} is in more than one range"),
+                ));
+            }
+            fs::create_dir_all(&new_range_path)?;
+            fs::rename(entry.path(), new_slot_path)?;
+            if let Some(slot_timings) = timings.remove(&slot) {
+                slot_timings.append(&new_range_path.join(TIMINGS_FILE_NAME))?;
+            }
+            new_ranges.insert(new_range);
+            summary.slots += 1;
+        }
+
+        // Timings of slots that were not saved, e.g. after a mismatch, move to the new range
+        // of their slot as well.
+        for (slot, slot_timings) in timings {
+            let new_range_path = partial_path.join(format_range(slot));
+            fs::create_dir_all(&new_range_path)?;
+            slot_timings.append(&new_range_path.join(TIMINGS_FILE_NAME))?;
+        }
+
+        if let Some(entry) = fs::read_dir(&old_path)?
+            .filter_map(Result::ok)
+            .find(|entry| entry.file_name() != TIMINGS_FILE_NAME)
+        {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("unexpected {} left in {old_range}", entry.path().display()),
+            ));
+        }
+        fs::remove_dir_all(&old_path)?;
+        StorageManager::remove_empty_parents(&storage_path, &old_path)?;
+        log::info!(
+            "Moved the slots of {old_range} to {} range(s)",
+            new_ranges.len()
+        );
+    }
+
+    for new_range in naming.list_ranges(&partial_path)? {
+        let new_path = partial_path.join(&new_range.name);
+        summary.new_ranges += 1;
+        // Timings appended by an interrupted run are present twice.
+        let timings_path = new_path.join(TIMINGS_FILE_NAME);
+        if timings_path.is_file() {
+            let mut timings = SlotTimings::load(&timings_path)?;
+            timings.sort_by_key(|slot_timings| slot_timings.slot);
+            timings.dedup_by_key(|slot_timings| slot_timings.slot);
+            SlotTimings::save(&timings_path, &mut timings)?;
+        }
+        StorageManager::move_folder(
+            &partial_path,
+            &new_path,
+            &storage_path.join(&new_range.name),
+        )?;
+    }
+
+    layout.save(workspace)?;
+    fs::remove_dir(partial_path)?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            cos_types::CosVersionedConfirmedBlockWithEntries,
+            geyser_plugin_cos_config::GeyserPluginCosConfig,
+            storage::Storage,
+            templates::{KeyTemplates, Naming},
+        },
+        serde_json::json,
+        solana_sdk::hash::Hash,
+        std::path::PathBuf,
+    };
+
+    /// A workspace with slots 0 to 24 committed in ranges of 10 slots, each with timings.
+    fn setup(name: &str) -> PathBuf {
+        let workspace =
+            std::env::temp_dir().join(format!("cos-rechunk-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&workspace);
+        let config: GeyserPluginCosConfig = serde_json::from_value(json!({
+            "workspace": workspace,
+            "genesis_hash": Hash::default().to_string(),
+            "slot_range": 10,
+            "commit_slot_delay": 0,
+        }))
+        .unwrap();
+        let storage = StorageManager::new(&config).unwrap();
+        for slot in 0..25 {
+            let block = CosVersionedConfirmedBlockWithEntries {
+                timings: SlotTimings::new(slot),
+                ..Default::default()
+            };
+            storage.save(slot, &block).unwrap();
+        }
+        storage.commit_all().unwrap();
+        workspace
+    }
+
+    fn with_slot_range(workspace: &Path, slot_range: u64) -> WorkspaceLayout {
+        let mut layout = WorkspaceLayout::load(workspace).unwrap().unwrap();
+        layout.slot_range = slot_range;
+        layout
+    }
+
+    /// The ranges in storage, with their slots and the slots of their timings.
+    fn list_ranges(workspace: &Path) -> Vec<(Slot, Slot, Vec<Slot>, Vec<Slot>)> {
+        let naming = Naming::default();
+        let storage_path = workspace.join("storage");
+        naming
+            .list_ranges(&storage_path)
+            .unwrap()
+            .into_iter()
+            .map(|range| {
+                let range_path = storage_path.join(&range.name);
+                let mut slots: Vec<Slot> = fs::read_dir(&range_path)
+                    .unwrap()
+                    .filter_map(|entry| {
+                        naming.parse_slot_folder(entry.unwrap().file_name().to_str()?)
+                    })
+                    .collect();
+                slots.sort_unstable();
+                let timings = SlotTimings::load(&range_path.join(TIMINGS_FILE_NAME))
+                    .unwrap()
+                    .iter()
+                    .map(|slot_timings| slot_timings.slot)
+                    .collect();
+                (range.start, range.end, slots, timings)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rechunk_fixed_ranges() {
+        let workspace = setup("fixed");
+        let summary = rechunk(&workspace, &with_slot_range(&workspace, 20)).unwrap();
+        assert_eq!(
+            (summary.slots, summary.old_ranges, summary.new_ranges),
+            (25, 3, 2)
+        );
+        assert_eq!(
+            list_ranges(&workspace),
+            vec![
+                (0, 20, (0..20).collect(), (0..20).collect()),
+                (20, 40, (20..25).collect(), (20..25).collect()),
+            ]
+        );
+        assert_eq!(
+            WorkspaceLayout::load(&workspace)
+                .unwrap()
+                .unwrap()
+                .slot_range,
+            20
+        );
+        assert!(!workspace.join(RECHUNK_FOLDER_NAME).exists());
+        fs::remove_dir_all(workspace).unwrap();
+    }
+
+    #[test]
+    fn test_rechunk_completes_an_interrupted_run() {
+        let workspace = setup("interrupted");
+        // A run moved half of the first range, with their timings, before it was interrupted.
+        let naming = Naming::default();
+        let old_path = workspace
+            .join("storage")
+            .join(naming.range_folder(0, Some(10), None));
+        let new_path = workspace
+            .join(RECHUNK_FOLDER_NAME)
+            .join(naming.range_folder(0, Some(20), None));
+        fs::create_dir_all(&new_path).unwrap();
+        for slot in 0..5 {
+            let slot_folder = naming.slot_folder(slot);
+            fs::rename(old_path.join(&slot_folder), new_path.join(&slot_folder)).unwrap();
+        }
+        for slot_timings in SlotTimings::load(&old_path.join(TIMINGS_FILE_NAME)).unwrap() {
+            slot_timings
+                .append(&new_path.join(TIMINGS_FILE_NAME))
+                .unwrap();
+        }
+
+        let summary = rechunk(&workspace, &with_slot_range(&workspace, 20)).unwrap();
+        assert_eq!((summary.slots, summary.new_ranges), (20, 2));
+        assert_eq!(
+            list_ranges(&workspace),
+            vec![
+                (0, 20, (0..20).collect(), (0..20).collect()),
+                (20, 40, (20..25).collect(), (20..25).collect()),
+            ]
+        );
+        fs::remove_dir_all(workspace).unwrap();
+    }
+
+    #[test]
+    fn test_rechunk_refuses_another_layout() {
+        let workspace = setup("mismatch");
+        let before = list_ranges(&workspace);
+
+        let mut layout = with_slot_range(&workspace, 20);
+        layout.genesis_hash = Some(Hash::new_unique().to_string());
+        let err = rechunk(&workspace, &layout).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the templates, the cluster and the identity of a workspace cannot be changed"
+        );
+        let mut layout = with_slot_range(&workspace, 20);
+        layout.templates = KeyTemplates {
+            slot_folder: "{slot}".to_string(),
+            ..Default::default()
+        };
+        assert!(rechunk(&workspace, &layout).is_err());
+
+        // Nor does it move the slots a plugin left in staging.
+        fs::create_dir_all(workspace.join("staging/range_0000000000000019_0000000000000023"))
+            .unwrap();
+        let err = rechunk(&workspace, &with_slot_range(&workspace, 20)).unwrap_err();
+        assert!(
+            err.to_string().contains("not committed by the plugin"),
+            "{err}"
+        );
+
+        assert_eq!(list_ranges(&workspace), before);
+        assert_eq!(
+            WorkspaceLayout::load(&workspace)
+                .unwrap()
+                .unwrap()
+                .slot_range,
+            10
+        );
+        fs::remove_dir_all(workspace).unwrap();
+    }
+}
@@ -0,0 +1,387 @@
+/// Account-state snapshot written from the startup account notifications.
+use {
+    crate::{
+        accounts::{AccountUpdate, AccountUpdates},
+        compression::{compress, decompress, CompressionMethod, CANONICAL_COMPRESSION_METHOD},
+        geyser_plugin_cos_config::GeyserPluginCosConfig,
+    },
+    prost::Message,
+    solana_sdk::{clock::Slot, hash::hash},
+    std::{
+        fs,
+        io::{self, ErrorKind},
+        path::{Path, PathBuf},
+        sync::Mutex,
+    },
+};
+
+/// Compression method of the snapshot chunks when none is configured. Picking the smallest
+/// output as for the cells would compress every chunk four times.
+const DEFAULT_SNAPSHOT_COMPRESSION_METHOD: CompressionMethod = CompressionMethod::Zstd;
+
+/// A chunk file of a snapshot.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SnapshotChunk {
+    /// File name of the chunk, relative to the snapshot folder.
+    pub file: String,
+    /// Number of accounts in the chunk.
+    pub accounts: u64,
+    /// Hash of the uncompressed protobuf encoding of the chunk.
+    pub content_hash: String,
+}
+
+/// The `manifest.json` file of a snapshot, written once every chunk is on disk.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct SnapshotManifest {
+    /// Slot of the snapshot the validator started from.
+    pub slot: Slot,
+    /// Number of accounts in the snapshot.
+    pub accounts: u64,
+    /// Sum of the lamports of all accounts, to compare with the bank capitalization.
+    pub lamports: u64,
+    pub chunks: Vec<SnapshotChunk>,
+    /// Name of the cluster, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<String>,
+    /// Genesis hash of the cluster, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genesis_hash: Option<String>,
+    /// Identity pubkey of the validator, if configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+}
+
+impl SnapshotManifest {
+    /// Reads the manifest of the snapshot in `path`.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(serde_json::from_slice(&fs::read(
+            path.join("manifest.json"),
+        )?)?)
+    }
+
+    /// Checks every chunk of the snapshot in `path` against the manifest.
+    /// Returns a description of each problem found.
+    pub fn verify(&self, path: &Path) -> io::Result<Vec<String>> {
+        let mut problems = vec![];
+        let mut accounts = 0;
+        let mut lamports = 0u64;
+        for chunk in &self.chunks {
+            let data = match fs::read(path.join(&chunk.file)).and_then(|data| decompress(&data)) {
+                Ok(data) => data,
+                Err(err) => {
+                    problems.push(format!("{}: {err}", chunk.file));
+                    continue;
+                }
+            };
+            if hash(&data).to_string() != chunk.content_hash {
+                problems.push(format!("{}: content hash mismatch", chunk.file));
+            }
+            match AccountUpdates::decode(&data[..]) {
+                Ok(updates) => {
+                    accounts += updates.accounts.len() as u64;
+                    lamports = updates.accounts.iter().fold(lamports, |sum, account| {
+                        sum.saturating_add(account.lamports)
+                    });
+                }
+                Err(err) => problems.push(format!("{}: {err}", chunk.file)),
+            }
+        }
+        if accounts != self.accounts {
+            problems.push(format!(
+                "{accounts} accounts in chunks, {} in manifest",
+                self.accounts
+            ));
+        }
+        if lamports != self.lamports {
+            problems.push(format!(
+                "{lamports} lamports in chunks, {} in manifest",
+                self.lamports
+            ));
+        }
+        Ok(problems)
+    }
+}
+
+#[derive(Default)]
+struct SnapshotState {
+    slot: Option<Slot>,
+    buffer: Vec<AccountUpdate>,
+    next_chunk: usize,
+    chunks: Vec<SnapshotChunk>,
+    accounts: u64,
+    lamports: u64,
+    finished: bool,
+}
+
+/// Writes the accounts notified during startup to
+/// `<workspace>/snapshots/snapshot_<slot>/accounts_<chunk>.proto`.
+///
+/// Startup notifications are sent from several threads, so chunks are compressed and
+/// written outside of the lock. The snapshot is written to a `.partial` folder that is
+/// renamed once the manifest is written, so a snapshot folder is always complete.
+pub struct SnapshotWriter {
+    snapshots_path: PathBuf,
+    compression: CompressionMethod,
+    chunk_size: usize,
+    /// Cluster and identity recorded in the manifest.
+    cluster: Option<String>,
+    genesis_hash: Option<String>,
+    identity: Option<String>,
+    state: Mutex<SnapshotState>,
+}
+
+impl SnapshotWriter {
+    pub fn new(config: &GeyserPluginCosConfig, chunk_size: usize) -> io::Result<Self> {
+        let snapshots_path = PathBuf::from(&config.workspace).join("snapshots");
+        fs::create_dir_all(&snapshots_path)?;
+        // Remove the snapshots that were interrupted by a restart.
+        for entry in fs::read_dir(&snapshots_path)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "partial")
+            {
+                fs::remove_dir_all(path)?;
+            }
+        }
+
+        let compression = match config.compression {
+            Some(compression) => compression,
+            None if config.canonical => CANONICAL_COMPRESSION_METHOD,
+            None => DEFAULT_SNAPSHOT_COMPRESSION_METHOD,
+        };
+
+        Ok(Self {
+            snapshots_path,
+            compression,
+            chunk_size: chunk_size.max(1),
+            cluster: config.cluster.clone(),
+            genesis_hash: config.genesis_hash.clone(),
+            identity: config.identity.clone(),
+            state: Mutex::default(),
+        })
+    }
+
+    pub fn format_snapshot(slot: Slot) -> String {
+        format!("snapshot_{slot:016x}")
+    }
+
+    fn partial_path(&self, slot: Slot) -> PathBuf {
+        self.snapshots_path
+            .join(format!("{}.partial", Self::format_snapshot(slot)))
+    }
+
+    /// Adds an account notified during startup, writing a chunk when enough accounts are
+    /// buffered.
+    pub fn add(&self, slot: Slot, account: AccountUpdate) -> io::Result<()> {
+        let chunk = {
+            let mut state = self.state.lock().unwrap();
+            if state.finished {
+                return Ok(());
+            }
+            match state.slot {
+                None => {
+                    fs::create_dir_all(self.partial_path(slot))?;
+                    state.slot = Some(slot);
+                }
+                Some(snapshot_slot) if snapshot_slot != slot => {
+                    log::warn!(
+                        "COS: Startup account for slot {slot} in snapshot of slot {snapshot_slot}"
+                    );
+                }
+                Some(_) => {}
+            }
+            state.accounts += 1;
+            state.lamports = state.lamports.saturating_add(account.lamports);
+            state.buffer.push(account);
+            if state.buffer.len() < self.chunk_size {
+                return Ok(());
+            }
+            Self::take_chunk(&mut state)
+        };
+        self.write_chunk(chunk)
+    }
+
+    /// Writes the remaining accounts and the manifest, then moves the snapshot in place.
+    /// Does nothing if no account was notified during startup.
+    pub fn finish(&self) -> io::Result<()> {
+        let chunk = {
+            let mut state = self.state.lock().unwrap();
+            if state.finished || state.slot.is_none() {
+                state.finished = true;
+                return Ok(());
+            }
+            state.finished = true;
+            Self::take_chunk(&mut state)
+        };
+        if !chunk.2.is_empty() {
+            self.write_chunk(chunk)?;
+        }
+
+        let state = self.state.lock().unwrap();
+        let slot = state.slot.unwrap();
+        let mut chunks = state.chunks.clone();
+        chunks.sort_by(|a, b| a.file.cmp(&b.file));
+        let manifest = SnapshotManifest {
+            slot,
+            accounts: state.accounts,
+            lamports: state.lamports,
+            chunks,
+            cluster: self.cluster.clone(),
+            genesis_hash: self.genesis_hash.clone(),
+            identity: self.identity.clone(),
+        };
+        let partial_path = self.partial_path(slot);
+        fs::write(
+            partial_path.join("manifest.json"),
+            serde_json::to_vec_pretty(&manifest)?,
+        )?;
+
+        let snapshot_path = self.snapshots_path.join(Self::format_snapshot(slot));
+        if snapshot_path.exists() {
+            fs::remove_dir_all(&snapshot_path)?;
+        }
+        fs::rename(partial_path, &snapshot_path)?;
+        log::info!(
+            "COS: Saved snapshot of slot {slot} with {} accounts to {}",
+            manifest.accounts,
+            snapshot_path.display()
+        );
+        Ok(())
+    }
+
+    fn take_chunk(state: &mut SnapshotState) -> (Slot, usize, Vec<AccountUpdate>) {
+        let index = state.next_chunk;
+        state.next_chunk += 1;
+        (
+            state.slot.unwrap_or_default(),
+            index,
+            std::mem::take(&mut state.buffer),
+        )
+    }
+
+    fn write_chunk(
+        &self,
+        (slot, index, accounts): (Slot, usize, Vec<AccountUpdate>),
+    ) -> io::Result<()> {
+        let count = accounts.len() as u64;
+        let chunk = AccountUpdates { accounts };
+        let mut buf = Vec::with_capacity(chunk.encoded_len());
+        chunk
+            .encode(&mut buf)
+            .map_err(|err| io::Error::new(ErrorKind::Other, err))?;
+        let file = format!("accounts_{index:08x}.proto");
+        fs::write(
+            self.partial_path(slot).join(&file),
+            compress(self.compression, &buf)?,
+        )?;
+
+        self.state.lock().unwrap().chunks.push(SnapshotChunk {
+            file,
+            accounts: count,
+            content_hash: hash(&buf).to_string(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json};
+
+    fn account(lamports: u64) -> AccountUpdate {
+        AccountUpdate {
+            pubkey: vec![lamports as u8; 32],
+            lamports,
+            data: vec![1; 100],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_snapshot_is_moved_in_place_once_complete() {
+        let workspace = std::env::temp_dir().join(format!("cos-snapshot-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&workspace);
+        let config: GeyserPluginCosConfig = serde_json::from_value(json!({
+            "workspace": workspace,
+            "slot_range": 10,
+            "commit_slot_delay": 5,
+            "cluster": "devnet",
+        }))
+        .unwrap();
+        // A snapshot interrupted by a restart.
+        let interrupted_path = workspace.join("snapshots/snapshot_0000000000000001.partial");
+        fs::create_dir_all(&interrupted_path).unwrap();
+
+        let writer = SnapshotWriter::new(&config, 2).unwrap();
+        assert!(!interrupted_path.exists());
+        for lamports in 1..=5 {
+            writer.add(100, account(lamports)).unwrap();
+        }
+        let partial_path = workspace.join("snapshots/snapshot_0000000000000064.partial");
+        let snapshot_path = workspace.join("snapshots/snapshot_0000000000000064");
+        assert!(partial_path.join("accounts_00000001.proto").is_file());
+        assert!(!snapshot_path.exists());
+
+        writer.finish().unwrap();
+        assert!(!partial_path.exists());
+        let manifest = SnapshotManifest::load(&snapshot_path).unwrap();
+        assert_eq!(
+            (manifest.slot, manifest.accounts, manifest.lamports),
+            (100, 5, 15)
+        );
+        assert_eq!(manifest.cluster.as_deref(), Some("devnet"));
+        let chunks: Vec<_> = manifest
+            .chunks
+            .iter()
+            .map(|chunk| (chunk.file.as_str(), chunk.accounts))
+            .collect();
+        assert_eq!(
+            chunks,
+            vec![
+                ("accounts_00000000.proto", 2),
+                ("accounts_00000001.proto", 2),
+                ("accounts_00000002.proto", 1),
+            ]
+        );
+        assert_eq!(
+            manifest.verify(&snapshot_path).unwrap(),
+            Vec::<String>::new()
+        );
+
+        // Accounts notified once the snapshot is written are left out.
+        writer.add(100, account(6)).unwrap();
+        assert_eq!(SnapshotManifest::load(&snapshot_path).unwrap().accounts, 5);
+
+        // A truncated chunk is reported, with the accounts and lamports missing.
+        let chunk_path = snapshot_path.join("accounts_00000001.proto");
+        let data = fs::read(&chunk_path).unwrap();
+        fs::write(&chunk_path, &data[..data.len() / 2]).unwrap();
+        let problems = manifest.verify(&snapshot_path).unwrap();
+        assert_eq!(problems.len(), 3, "{problems:?}");
+        assert!(problems[0].starts_with("accounts_00000001.proto: "));
+        assert_eq!(problems[1], "3 accounts in chunks, 5 in manifest");
+        assert_eq!(problems[2], "8 lamports in chunks, 15 in manifest");
+        fs::remove_dir_all(workspace).unwrap();
+    }
+
+    #[test]
+    fn test_no_snapshot_without_startup_accounts() {
+        let workspace =
+            std::env::temp_dir().join(format!("cos-snapshot-empty-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&workspace);
+        let config: GeyserPluginCosConfig = serde_json::from_value(json!({
+            "workspace": workspace,
+            "slot_range": 10,
+            "commit_slot_delay": 5,
+        }))
+        .unwrap();
+        SnapshotWriter::new(&config, 2).unwrap().finish().unwrap();
+        assert_eq!(
+            fs::read_dir(workspace.join("snapshots")).unwrap().count(),
+            0
+        );
+        fs::remove_dir_all(workspace).unwrap();
+    }
+}
@@ -0,0 +1,237 @@
+/// Health of the plugin, written periodically to `status.json` in the workspace for the
+/// monitoring agents and the syncer that cannot reach the metrics endpoint.
+use {
+    crate::{
+        datastore::Datastore, periodic::PeriodicTask, storage::StorageManager, watchdog::Watchdog,
+    },
+    serde::{Deserialize, Serialize},
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_sdk::clock::Slot,
+    std::{
+        fs, io,
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    },
+};
+
+pub const STATUS_FILE_NAME: &str = "status.json";
+
+/// Content of `status.json`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct PluginStatus {
+    /// When the file was written, in RFC 3339 format.
+    pub updated_at: String,
+    /// Version of the plugin crate.
+    pub plugin_version: String,
+    /// Hash of the loaded configuration, changing whenever a setting changes.
+    pub config_version: String,
+    /// Whether writes are paused through the admin API.
+    pub paused: bool,
+    /// First slot archived, aligned on a slot range unless `start_slot` is configured.
+    pub first_slot: Option<Slot>,
+    /// Last slot of the capture window, if `end_slot` is configured.
+    #[serde(default)]
+    pub end_slot: Option<Slot>,
+    /// Set once every slot up to `end_slot` is committed and the plugin is idle.
+    #[serde(default)]
+    pub capture_complete: bool,
+    pub latest_rooted_slot: Option<Slot>,
+    pub last_saved_slot: Option<Slot>,
+    pub last_committed_slot: Option<Slot>,
+    /// Slot range folders in staging, waiting to be committed.
+    pub pending_ranges: Vec<String>,
+    /// Number of slots in the in-memory cache.
+    pub cache_size: usize,
+    pub last_error: Option<LastError>,
+    /// Watchdog state of each kind of notification.
+    pub events: Vec<EventStatus>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LastError {
+    pub message: String,
+    /// When the error was returned to the validator, in RFC 3339 format.
+    pub timestamp: String,
+}
+
+/// Watchdog state of one kind of event, reported in `status.json`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EventStatus {
+    pub event: String,
+    /// Seconds since the last event, or since the end of startup if there was none yet.
+    pub seconds_since_last: Option<u64>,
+    /// Seconds without event after which the stream is considered stalled, 0 if disabled.
+    pub threshold_secs: u64,
+    pub stalled: bool,
+}
+
+impl PluginStatus {
+    /// Read the status file of a workspace.
+    pub fn load(workspace: &Path) -> io::Result<Self> {
+        let status = fs::read(workspace.join(STATUS_FILE_NAME))?;
+        serde_json::from_slice(&status).map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    }
+}
+
+static LAST_ERROR: Mutex<Option<LastError>> = Mutex::new(None);
+
+/// Remembers the error returned to the validator, reported by the next status update.
+pub(crate) fn record_error(err: &GeyserPluginError) {
+    *LAST_ERROR.lock().unwrap() = Some(LastError {
+        message: err.to_string(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+    });
+}
+
+/// Sources of the status, shared with the plugin.
+pub(crate) struct StatusSource {
+    pub datastore: Arc<Mutex<Datastore>>,
+    pub storage: Arc<StorageManager>,
+    pub paused: Arc<AtomicBool>,
+    pub watchdog: Arc<Watchdog>,
+    pub config_version: String,
+}
+
+impl StatusSource {
+    fn status(&self) -> io::Result<PluginStatus> {
+        let (first_slot, end_slot, capture_complete, latest_rooted_slot, cache_size) = {
+            let datastore = self.datastore.lock().unwrap();
+            (
+                datastore.first_slot(),
+                datastore.end_slot(),
+                datastore.is_complete(),
+                datastore.latest_rooted_slot(),
+                datastore.cache_size(),
+            )
+        };
+        Ok(PluginStatus {
+            updated_at: chrono::Utc::now().to_rfc3339(),
+            plugin_version: env!("CARGO_PKG_VERSION").to_string(),
+            config_version: self.config_version.clone(),
+            paused: self.paused.load(Ordering::Relaxed),
+            first_slot,
+            end_slot,
+            capture_complete,
+            latest_rooted_slot,
+            last_saved_slot: self.storage.last_saved_slot(),
+            last_committed_slot: self.storage.last_committed_slot(),
+            pending_ranges: self.storage.pending_ranges()?,
+            cache_size,
+            last_error: LAST_ERROR.lock().unwrap().clone(),
+            events: self.watchdog.events(),
+        })
+    }
+
+    /// Writes the status to a temporary file renamed over `path`, so readers never see a
+    /// partial file.
+    fn write(&self, path: &Path) -> io::Result<()> {
+        let status = serde_json::to_vec_pretty(&self.status()?)?;
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, status)?;
+        fs::rename(tmp_path, path)
+    }
+}
+
+/// Writes `status.json` on a background thread every interval, and once more when dropped.
+pub(crate) struct StatusWriter {
+    _task: PeriodicTask,
+}
+
+impl StatusWriter {
+    pub fn start(workspace: &str, interval: Duration, source: StatusSource) -> io::Result<Self> {
+        let path = PathBuf::from(workspace).join(STATUS_FILE_NAME);
+        let task = PeriodicTask::start("cosStatus", interval, move || {
+            if let Err(err) = source.write(&path) {
+                log::warn!("COS: Failed to write {}: {err}", path.display());
+            }
+        })?;
+        Ok(Self { _task: task })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            cos_types::CosVersionedConfirmedBlockWithEntries,
+            geyser_plugin_cos_config::GeyserPluginCosConfig, storage::Storage,
+        },
+        serde_json::json,
+        solana_sdk::hash::Hash,
+    };
+
+    #[test]
+    fn test_status_file() {
+        let workspace = std::env::temp_dir().join(format!("cos-status-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&workspace);
+        let config: GeyserPluginCosConfig = serde_json::from_value(json!({
+            "workspace": workspace,
+            "genesis_hash": Hash::default().to_string(),
+            "slot_range": 10,
+            "commit_slot_delay": 5,
+            "end_slot": 99,
+        }))
+        .unwrap();
+        let storage = StorageManager::new(&config).unwrap();
+        storage
+            .save(20, &CosVersionedConfirmedBlockWithEntries::default())
+            .unwrap();
+        let mut datastore = Datastore::new(&config);
+        datastore.check_first_slot(20).unwrap();
+        datastore.get_mut_entry(25);
+        datastore.set_rooted(25);
+        let source = StatusSource {
+            datastore: Arc::new(Mutex::new(datastore)),
+            storage: Arc::new(storage),
+            paused: Arc::new(AtomicBool::new(true)),
+            watchdog: Arc::new(Watchdog::new(&config.watchdog)),
+            config_version: config.version(),
+        };
+
+        // A previous status is replaced as a whole.
+        let path = workspace.join(STATUS_FILE_NAME);
+        fs::write(&path, "{\"paused\": fa").unwrap();
+        source.write(&path).unwrap();
+        assert!(!path.with_extension("json.tmp").exists());
+        let status = PluginStatus::load(&workspace).unwrap();
+        assert_eq!(status.plugin_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(status.config_version, config.version());
+        assert!(status.paused);
+        assert_eq!(
+            (status.first_slot, status.end_slot, status.capture_complete),
+            (Some(20), Some(99), false)
+        );
+        assert_eq!(
+            (
+                status.latest_rooted_slot,
+                status.last_saved_slot,
+                status.last_committed_slot
+            ),
+            (Some(25), Some(20), None)
+        );
+        assert_eq!(
+            status.pending_ranges,
+            vec!["range_0000000000000014_000000000000001e"]
+        );
+        assert_eq!(status.cache_size, 1);
+        assert!(chrono::DateTime::parse_from_rfc3339(&status.updated_at).is_ok());
+
+        // The writer updates the file once more when it stops.
+        fs::remove_file(&path).unwrap();
+        let writer = StatusWriter::start(
+            workspace.to_str().unwrap(),
+            Duration::from_secs(3600),
+            source,
+        )
+        .unwrap();
+        drop(writer);
+        assert!(PluginStatus::load(&workspace).unwrap().paused);
+        fs::remove_dir_all(workspace).unwrap();
+    }
+}
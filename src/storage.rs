@@ -1,20 +1,33 @@
-use crate::compression::compress_best;
+use crate::accounts::AccountUpdates;
+use crate::compression::{
+    compress, compress_best, compression_method, CompressionMethod, CANONICAL_COMPRESSION_METHOD,
+};
 use crate::cos_types::{
     CosVersionedConfirmedBlockWithEntries, CosVersionedTransactionWithStatusMeta, RowData, RowKey,
     RowType,
 };
+use crate::filters::TransactionCounts;
 use crate::geyser_plugin_cos_config::GeyserPluginCosConfig;
-use solana_sdk::clock::Slot;
+use crate::layout::RangePolicy;
+use crate::lock::WorkspaceLock;
+use crate::metrics::metrics;
+use crate::templates::{Naming, RangeFolder};
+use crate::timings::{now_millis, SlotTimings, TIMINGS_FILE_NAME};
+use crate::votes::{VoteTransaction, VoteTransactions};
+use solana_sdk::clock::{Slot, UnixTimestamp};
+use solana_sdk::hash::{hash, Hash};
 use solana_sdk::instruction::CompiledInstruction;
 use solana_sdk::message::AccountKeys;
 use solana_sdk::pubkey::Pubkey;
 use solana_storage_proto::convert::{entries, generated};
 use solana_transaction_status::extract_memos::ExtractMemos;
 use solana_transaction_status::EntrySummary;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::OpenOptions;
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 
 enum KeyType<'a> {
     MemoProgram,
@@ -22,7 +35,9 @@ enum KeyType<'a> {
     Unknown(&'a Pubkey),
 }
 
+/// Destination of the rooted slots.
 pub trait Storage {
+    /// Saves a rooted slot. The slot ranges that are complete are made ready for upload.
     fn save(
         &self,
         slot: Slot,
@@ -35,11 +50,68 @@ pub trait Storage {
 pub struct StorageManager {
     /// The number of slots in each range.
     slot_range: u64,
+    /// How slots are grouped into ranges.
+    range_policy: RangePolicy,
+    /// Names of the folders and cells.
+    naming: Naming,
+    /// Ranges of the size policy, shared with the storage it is reloaded as.
+    size_ranges: Arc<Mutex<SizeRanges>>,
+    /// Block time of each range, by first slot, when the range folders are named after it.
+    /// Shared with the storage it is reloaded as.
+    range_times: Arc<Mutex<HashMap<Slot, Option<UnixTimestamp>>>>,
     /// Commit slot delay in number of slots.
     commit_slot_delay: u64,
+    /// Fixed compression method, or `None` to pick the smallest output.
+    compression: Option<CompressionMethod>,
+    /// Write deterministic cells and their content hashes.
+    canonical: bool,
+    /// Record the transaction counts of each slot, as transactions may be filtered.
+    record_transaction_counts: bool,
+    /// Store vote transactions in the `votes` table.
+    separate_votes: bool,
+    /// Highest slot saved to staging, or 0 if none.
+    last_saved_slot: AtomicU64,
+    /// Last slot of the latest range moved to storage, or 0 if none.
+    last_committed_slot: AtomicU64,
     /// RWLock to ensure only one thread is writing to "storage" at a time.
     /// Multiple threads can write to "staging" concurrently.
     rw_lock: RwLock<(PathBuf, PathBuf)>,
+    /// Exclusive lock on the workspace, shared with the storage it is reloaded as.
+    workspace_lock: Option<Arc<WorkspaceLock>>,
+}
+
+/// Ranges of the size policy.
+#[derive(Default)]
+struct SizeRanges {
+    /// The range being filled, if any.
+    open: Option<OpenRange>,
+    /// First slot and end slot (exclusive) of the ranges committed before the workspace was
+    /// opened, and of the ranges closed since.
+    closed: BTreeMap<Slot, Slot>,
+}
+
+#[derive(Clone, Copy)]
+struct OpenRange {
+    start: Slot,
+    /// Highest slot saved to the range.
+    last_slot: Slot,
+    /// Size of the cells saved to the range.
+    bytes: u64,
+}
+
+/// Total size of the files in `path` and its subfolders.
+fn folder_size(path: &Path) -> std::io::Result<u64> {
+    let mut bytes = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        bytes += if metadata.is_dir() {
+            folder_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(bytes)
 }
 
 impl Storage for StorageManager {
@@ -49,33 +121,153 @@ impl Storage for StorageManager {
         slot: Slot,
         confirmed_block: &CosVersionedConfirmedBlockWithEntries,
     ) -> std::io::Result<()> {
+        let is_size_policy = matches!(self.range_policy, RangePolicy::Size { .. });
+        if is_size_policy {
+            self.close_range_before(slot)?;
+        }
         self.save_to_staging(slot, confirmed_block)?;
+        if is_size_policy {
+            self.record_slot_size(slot)?;
+        }
+        self.last_saved_slot.fetch_max(slot, Ordering::Relaxed);
         self.commit_to_storage(slot)
     }
 }
 
 impl StorageManager {
+    /// Creates the `storage` and `staging` folders of the workspace. The `staging` folder is
+    /// emptied, as the ranges left over by a previous run are incomplete.
+    ///
+    /// The workspace is locked until the storage is dropped, and an error is returned if
+    /// another process holds the lock.
     pub fn new(config: &GeyserPluginCosConfig) -> std::io::Result<Self> {
-        let slot_range = config.slot_range;
+        Self::with_lock(
+            config,
+            WorkspaceLock::acquire(Path::new(&config.workspace))?,
+        )
+    }
+
+    /// Same as [`Self::new`], with the lock on the workspace taken by the caller.
+    pub(crate) fn with_lock(
+        config: &GeyserPluginCosConfig,
+        workspace_lock: WorkspaceLock,
+    ) -> std::io::Result<Self> {
+        // Ensure clean staging directory
+        let staging_path = PathBuf::from(config.workspace.to_string()).join("staging");
+        if Path::exists(&staging_path) {
+            std::fs::remove_dir_all(&staging_path)?;
+        }
+        Ok(Self {
+            workspace_lock: Some(Arc::new(workspace_lock)),
+            ..Self::open(config)?
+        })
+    }
+
+    /// Opens the workspace for an offline tool, such as the backfill. Unlike [`Self::new`],
+    /// the `staging` folder is never emptied: an error is returned if it holds slots, which
+    /// a plugin saved but did not commit, or if another process holds the lock.
+    pub fn new_offline(config: &GeyserPluginCosConfig) -> std::io::Result<Self> {
+        let workspace_lock = WorkspaceLock::acquire(Path::new(&config.workspace))?;
+        Self::check_staging_is_empty(Path::new(&config.workspace))?;
+        Ok(Self {
+            workspace_lock: Some(Arc::new(workspace_lock)),
+            ..Self::open(config)?
+        })
+    }
+
+    /// Fails if the `staging` folder of `workspace` holds slots, which a plugin saved but did
+    /// not commit, so that the offline tools never discard them.
+    pub(crate) fn check_staging_is_empty(workspace: &Path) -> std::io::Result<()> {
+        let staging_path = workspace.join("staging");
+        if staging_path.is_dir() && std::fs::read_dir(&staging_path)?.next().is_some() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "{} holds slots not committed by the plugin, which discards them when it \
+                     starts: remove the folder to go on without them",
+                    staging_path.display()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Applies `config` to the workspace of this storage, keeping the ranges in staging, when
+    /// the plugin is reloaded with its cached slots. The workspace and slot range must not
+    /// change.
+    pub fn reload(&self, config: &GeyserPluginCosConfig) -> std::io::Result<Self> {
+        let storage = Self {
+            workspace_lock: self.workspace_lock.clone(),
+            size_ranges: self.size_ranges.clone(),
+            range_times: self.range_times.clone(),
+            ..Self::open(config)?
+        };
+        storage.last_saved_slot.store(
+            self.last_saved_slot.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        storage.last_committed_slot.store(
+            self.last_committed_slot.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        Ok(storage)
+    }
+
+    fn open(config: &GeyserPluginCosConfig) -> std::io::Result<Self> {
+        let layout = config.layout();
+        let slot_range = layout.slot_range;
+        let range_policy = layout.range_policy.clone();
+        if slot_range == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "slot_range must be greater than 0",
+            ));
+        }
 
         // Ensure the storage directory exists
         let ready_path = PathBuf::from(config.workspace.to_string()).join("storage");
         let staging_path = PathBuf::from(config.workspace.to_string()).join("staging");
         let commit_slot_delay = config.commit_slot_delay;
+        let canonical = config.canonical;
+        let record_transaction_counts = config.transaction_filter.is_some();
+        let separate_votes = config.separate_votes;
+        let compression = match config.compression {
+            None if canonical => Some(CANONICAL_COMPRESSION_METHOD),
+            compression => compression,
+        };
 
         std::fs::create_dir_all(&ready_path)?;
-        // Ensure clean staging directory
-        if Path::exists(&staging_path) {
-            std::fs::remove_dir_all(&staging_path)?;
-        }
         std::fs::create_dir_all(&staging_path)?;
+        let naming = layout.naming().map_err(|(field, msg)| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("invalid {field}: {msg}"),
+            )
+        })?;
+        layout.check_or_record(Path::new(&config.workspace))?;
+
+        // New ranges of the size policy must not overlap the committed ones.
+        let mut size_ranges = SizeRanges::default();
+        if matches!(range_policy, RangePolicy::Size { .. }) {
+            for range in naming.list_ranges(&ready_path)? {
+                size_ranges.closed.insert(range.start, range.end);
+            }
+        }
 
         let rw_lock = RwLock::new((ready_path, staging_path));
 
         Ok(StorageManager {
             slot_range,
+            range_policy,
+            naming,
             commit_slot_delay,
+            compression,
+            canonical,
+            record_transaction_counts,
+            separate_votes,
+            size_ranges: Arc::new(Mutex::new(size_ranges)),
             rw_lock,
+            ..Default::default()
         })
     }
 
@@ -90,6 +282,11 @@ impl StorageManager {
         let CosVersionedConfirmedBlockWithEntries {
             block: confirmed_block,
             entries,
+            accounts,
+            filtered_transaction_indexes,
+            vote_transaction_indexes,
+            executed_transaction_count,
+            timings,
             ..
         } = confirmed_block;
 
@@ -110,29 +307,155 @@ impl StorageManager {
             },
         )];
 
-        let blocks_cells = [(
-            Self::slot_to_blocks_key(slot),
-            confirmed_block.clone().into(),
+        let mut sorted_accounts = accounts.clone();
+        // Writes to the same account must be replayed in write version order.
+        sorted_accounts.sort_by_key(|account| account.write_version);
+        let account_cells = [(
+            Self::slot_to_accounts_key(slot),
+            AccountUpdates {
+                accounts: sorted_accounts.into_iter().map(Into::into).collect(),
+            },
         )];
 
+        let mut confirmed_block = confirmed_block.clone();
+        // Transactions are stored by index, so the filtered ones leave empty slots behind. Only
+        // the slots of the filtered transactions are dropped: a lost transaction keeps its
+        // empty slot, so that the indexes of the other ones do not shift.
+        let transactions: Vec<_> = std::mem::take(&mut confirmed_block.transactions)
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| {
+                !(self.record_transaction_counts && filtered_transaction_indexes.contains(index))
+            })
+            .map(|(index, transaction)| (vote_transaction_indexes.contains(&index), transaction))
+            .collect();
+        let transaction_counts = TransactionCounts {
+            executed: *executed_transaction_count,
+            archived: transactions
+                .iter()
+                .filter(|(_, transaction)| !transaction.transaction.signatures.is_empty())
+                .count() as u64,
+            filtered: filtered_transaction_indexes.len() as u64,
+        };
+        let mut vote_transactions = vec![];
+        for (position, (is_vote, transaction)) in transactions.into_iter().enumerate() {
+            if is_vote && self.separate_votes {
+                vote_transactions.push(VoteTransaction {
+                    position: position as u64,
+                    transaction: Some(transaction.into()),
+                });
+            } else {
+                confirmed_block.transactions.push(transaction);
+            }
+        }
+        let vote_cells = [(
+            Self::slot_to_votes_key(slot),
+            VoteTransactions {
+                transactions: vote_transactions,
+            },
+        )];
+        // Without a transaction filter, the counts are neither recorded nor checked.
+        if self.record_transaction_counts
+            && transaction_counts.archived + transaction_counts.filtered
+                != transaction_counts.executed
+        {
+            log::warn!("COS: Slot {slot} transaction counts mismatch {transaction_counts:?}");
+            tracing::warn!(
+                executed = transaction_counts.executed,
+                archived = transaction_counts.archived,
+                filtered = transaction_counts.filtered,
+                "Transaction counts mismatch"
+            );
+        } else {
+            tracing::info!(
+                executed = transaction_counts.executed,
+                archived = transaction_counts.archived,
+                filtered = transaction_counts.filtered,
+                entries = entries.len(),
+                "Validated"
+            );
+        }
+        if self.canonical {
+            // Rewards are the only part of the block whose order may differ between validators.
+            confirmed_block.rewards.sort_by(|a, b| {
+                (&a.pubkey, a.reward_type.map(|t| t as u8), a.lamports).cmp(&(
+                    &b.pubkey,
+                    b.reward_type.map(|t| t as u8),
+                    b.lamports,
+                ))
+            });
+        }
+
+        let block_time = confirmed_block.block_time;
+        let blocks_cells = [(Self::slot_to_blocks_key(slot), confirmed_block.into())];
+
         let _r_lock = self.rw_lock.read().unwrap();
-        let (_, staging_path) = &*_r_lock;
+        let (ready_path, staging_path) = &*_r_lock;
+        self.record_range_time(ready_path, slot, block_time)?;
+        let slot_path = staging_path
+            .join(self.range_folder(slot))
+            .join(self.naming.slot_folder(slot));
 
+        let mut content_hashes = BTreeMap::new();
         if !entries.is_empty() {
-            self.put_protobuf_cells::<entries::Entries>(
-                staging_path,
+            content_hashes.extend(self.put_protobuf_cells::<entries::Entries>(
+                &slot_path,
                 slot,
+                block_time,
                 "entries",
                 &entry_cells,
-            )?;
+            )?);
         }
 
-        self.put_protobuf_cells::<generated::ConfirmedBlock>(
-            staging_path,
+        if !accounts.is_empty() {
+            content_hashes.extend(self.put_protobuf_cells::<AccountUpdates>(
+                &slot_path,
+                slot,
+                block_time,
+                "accounts",
+                &account_cells,
+            )?);
+        }
+
+        if !vote_cells[0].1.transactions.is_empty() {
+            content_hashes.extend(self.put_protobuf_cells::<VoteTransactions>(
+                &slot_path,
+                slot,
+                block_time,
+                "votes",
+                &vote_cells,
+            )?);
+        }
+
+        content_hashes.extend(self.put_protobuf_cells::<generated::ConfirmedBlock>(
+            &slot_path,
             slot,
+            block_time,
             "blocks",
             &blocks_cells,
-        )
+        )?);
+
+        if self.canonical {
+            Self::save_content_hashes(&slot_path, &content_hashes)?;
+        }
+        if self.record_transaction_counts {
+            Self::save_transaction_counts(&slot_path, &transaction_counts)?;
+        }
+        // Slots rebuilt from a ledger or an RPC node have no timings.
+        if timings.first_seen.is_some() {
+            let mut timings = timings.clone();
+            timings.staged = Some(now_millis());
+            metrics().record_stage("confirmed", &timings, timings.confirmed);
+            metrics().record_stage("rooted", &timings, timings.rooted);
+            metrics().record_stage("staged", &timings, timings.staged);
+            timings.append(
+                &staging_path
+                    .join(self.range_folder(slot))
+                    .join(TIMINGS_FILE_NAME),
+            )?;
+        }
+        tracing::info!("Staged");
+        Ok(())
     }
 
     /// Copy the interval containing "slot" from staging to ready folder.
@@ -141,32 +464,338 @@ impl StorageManager {
         // The trigger to copy the staging directory to the storage directory is to receive
         // a slot that is a multiple of the slot range plus a constant factor.
         // When this slot is received, we copy all previous slot ranges from staging to storage.
-        if current_slot % self.slot_range != self.commit_slot_delay {
+        let Some((start_slot, _)) = self.range_policy.range(current_slot, self.slot_range) else {
+            return self.commit_size_ranges(false);
+        };
+        if current_slot - start_slot != self.commit_slot_delay {
             return Ok(());
         }
+        self.commit_ranges_before(current_slot)
+    }
+
+    /// Move every slot range from staging to storage, except the one still being written,
+    /// i.e. the range of the highest slot saved. Under the size policy, the ranges that are
+    /// closed are moved.
+    pub fn commit_completed(&self) -> std::io::Result<()> {
+        if matches!(self.range_policy, RangePolicy::Size { .. }) {
+            return self.commit_size_ranges(false);
+        }
+        match self.last_saved_slot() {
+            Some(last_saved_slot) => self.commit_ranges_before(last_saved_slot),
+            None => Ok(()),
+        }
+    }
+
+    /// Move every slot range from staging to storage, except the one containing `current_slot`.
+    fn commit_ranges_before(&self, current_slot: Slot) -> std::io::Result<()> {
+        let current_slot_range_str = self.range_folder(current_slot);
 
         let _w_lock = self.rw_lock.write().unwrap();
         let (ready_path, staging_path) = &*_w_lock;
 
         // Read all slot ranges from staging and move them to storage,
         // all except the current one.
-        let current_slot_range_str = Self::format_slot_range(current_slot, self.slot_range);
-        for entry in std::fs::read_dir(staging_path)? {
-            let slot_range_path = entry?.path();
-
-            if let Some(folder_name) = slot_range_path.file_name() {
-                if let Some(folder_name) = folder_name.to_str() {
-                    if folder_name != current_slot_range_str {
-                        let storage_folder_path = ready_path.join(folder_name);
-                        // Move the staging directory to the storage directory
-                        std::fs::rename(&slot_range_path, &storage_folder_path)?;
-                    }
+        for range in self.naming.list_ranges(staging_path)? {
+            if range.name != current_slot_range_str {
+                // Move the staging directory to the storage directory
+                self.move_range(staging_path, ready_path, &range)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move every slot range from staging to storage, including the current one.
+    /// Used by offline tools once they are done writing.
+    pub fn commit_all(&self) -> std::io::Result<()> {
+        if matches!(self.range_policy, RangePolicy::Size { .. }) {
+            return self.commit_size_ranges(true);
+        }
+        let _w_lock = self.rw_lock.write().unwrap();
+        let (ready_path, staging_path) = &*_w_lock;
+
+        for range in self.naming.list_ranges(staging_path)? {
+            self.move_range(staging_path, ready_path, &range)?;
+        }
+        Ok(())
+    }
+
+    /// Closes the open range of the size policy once it is full, or if `close` is set, and
+    /// moves every range but the open one from staging to storage. Ranges are committed as
+    /// soon as they are closed, as the slots are saved in order.
+    fn commit_size_ranges(&self, close: bool) -> std::io::Result<()> {
+        let RangePolicy::Size { target_bytes } = self.range_policy else {
+            return Ok(());
+        };
+        let _w_lock = self.rw_lock.write().unwrap();
+        let (ready_path, staging_path) = &*_w_lock;
+        let mut size_ranges = self.size_ranges.lock().unwrap();
+
+        if let Some(open) = size_ranges.open.filter(|open| {
+            close
+                || open.bytes >= target_bytes
+                || open.last_slot - open.start >= self.slot_range - 1
+        }) {
+            let end_slot = open.last_slot + 1;
+            let open_path = staging_path.join(self.range_folder_of(open.start, None));
+            if open_path.exists() {
+                Self::move_folder(
+                    staging_path,
+                    &open_path,
+                    &staging_path.join(self.range_folder_of(open.start, Some(end_slot))),
+                )?;
+            }
+            size_ranges.closed.insert(open.start, end_slot);
+            size_ranges.open = None;
+        }
+
+        // The open range has no end slot, so it is not listed.
+        for range in self.naming.list_ranges(staging_path)? {
+            self.move_range(staging_path, ready_path, &range)?;
+        }
+        Ok(())
+    }
+
+    /// Closes the open range of the size policy if `slot` comes after a closed range that
+    /// starts within it, e.g. a range committed before a restart, so that the ranges never
+    /// overlap.
+    fn close_range_before(&self, slot: Slot) -> std::io::Result<()> {
+        let must_close = {
+            let size_ranges = self.size_ranges.lock().unwrap();
+            size_ranges.open.is_some_and(|open| {
+                slot > open.start
+                    && size_ranges
+                        .closed
+                        .range(open.start + 1..=slot)
+                        .next_back()
+                        .is_some_and(|(_, end_slot)| slot >= *end_slot)
+            })
+        };
+        if must_close {
+            self.commit_size_ranges(true)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the size of the cells of `slot` to its range, under the size policy.
+    fn record_slot_size(&self, slot: Slot) -> std::io::Result<()> {
+        let _r_lock = self.rw_lock.read().unwrap();
+        let (_, staging_path) = &*_r_lock;
+        let range_folder = self.range_folder(slot);
+        let folder_path = staging_path
+            .join(&range_folder)
+            .join(self.naming.slot_folder(slot));
+        let bytes = folder_size(&folder_path)?;
+
+        let mut size_ranges = self.size_ranges.lock().unwrap();
+        // Slots saved to a closed range do not count.
+        if let Some(open) = size_ranges
+            .open
+            .as_mut()
+            .filter(|open| range_folder == self.range_folder_of(open.start, None))
+        {
+            open.bytes += bytes;
+            open.last_slot = open.last_slot.max(slot);
+        }
+        Ok(())
+    }
+
+    /// Path of the folder of the range containing `slot`.
+    fn range_folder(&self, slot: Slot) -> String {
+        let (start_slot, end_slot) = self.range_bounds(slot);
+        self.range_folder_of(start_slot, end_slot)
+    }
+
+    /// Path of the folder of the range from `start_slot` to `end_slot` (exclusive), or of the
+    /// open range of the size policy if `end_slot` is `None`.
+    fn range_folder_of(&self, start_slot: Slot, end_slot: Option<Slot>) -> String {
+        let block_time = if self.naming.range_uses_block_time() {
+            self.range_times
+                .lock()
+                .unwrap()
+                .get(&start_slot)
+                .copied()
+                .flatten()
+        } else {
+            None
+        };
+        self.naming.range_folder(start_slot, end_slot, block_time)
+    }
+
+    /// First slot and end slot (exclusive) of the range containing `slot`, with no end slot
+    /// for the open range of the size policy. Under the size policy, a slot after the ranges
+    /// closed so far opens a new range if none is open, and a slot before them that is in
+    /// none of them, e.g. a slot saved late, gets a range of its own.
+    fn range_bounds(&self, slot: Slot) -> (Slot, Option<Slot>) {
+        if let Some((start_slot, end_slot)) = self.range_policy.range(slot, self.slot_range) {
+            return (start_slot, Some(end_slot));
+        }
+        let mut size_ranges = self.size_ranges.lock().unwrap();
+        if let Some((start_slot, end_slot)) = size_ranges.closed.range(..=slot).next_back() {
+            if slot < *end_slot {
+                return (*start_slot, Some(*end_slot));
+            }
+        }
+        let open = *size_ranges.open.get_or_insert(OpenRange {
+            start: slot,
+            last_slot: slot,
+            bytes: 0,
+        });
+        if slot >= open.start {
+            (open.start, None)
+        } else {
+            (slot, Some(slot + 1))
+        }
+    }
+
+    /// Records the block time of the range of `slot` when the range folders are named after
+    /// it: the block time of the range folder already in storage, if any, or else the
+    /// `block_time` of the first slot saved to the range.
+    fn record_range_time(
+        &self,
+        ready_path: &Path,
+        slot: Slot,
+        block_time: Option<UnixTimestamp>,
+    ) -> std::io::Result<()> {
+        if !self.naming.range_uses_block_time() {
+            return Ok(());
+        }
+        let (start_slot, _) = self.range_bounds(slot);
+        if self.range_times.lock().unwrap().contains_key(&start_slot) {
+            return Ok(());
+        }
+        let block_time = match self
+            .naming
+            .list_ranges(ready_path)?
+            .into_iter()
+            .find(|range| range.start == start_slot)
+        {
+            Some(committed) => committed.block_time,
+            None => block_time,
+        };
+        self.range_times
+            .lock()
+            .unwrap()
+            .entry(start_slot)
+            .or_insert(block_time);
+        Ok(())
+    }
+
+    /// Highest slot saved to staging since the plugin was loaded.
+    pub fn last_saved_slot(&self) -> Option<Slot> {
+        Some(self.last_saved_slot.load(Ordering::Relaxed)).filter(|slot| *slot > 0)
+    }
+
+    /// Last slot of the latest range moved to storage since the plugin was loaded.
+    pub fn last_committed_slot(&self) -> Option<Slot> {
+        Some(self.last_committed_slot.load(Ordering::Relaxed)).filter(|slot| *slot > 0)
+    }
+
+    /// Paths of the slot range folders in staging, waiting to be committed.
+    pub fn pending_ranges(&self) -> std::io::Result<Vec<String>> {
+        let _r_lock = self.rw_lock.read().unwrap();
+        let (_, staging_path) = &*_r_lock;
+
+        let mut ranges: Vec<String> = self
+            .naming
+            .list_ranges(staging_path)?
+            .into_iter()
+            .map(|range| range.name)
+            .collect();
+        if let Some(open) = self.size_ranges.lock().unwrap().open {
+            let open_range_str = self.range_folder_of(open.start, None);
+            if staging_path.join(&open_range_str).is_dir() {
+                ranges.push(open_range_str);
+            }
+        }
+        ranges.sort();
+        Ok(ranges)
+    }
+
+    /// Move a slot range folder from staging to storage. If the destination already exists
+    /// (e.g. a range that was committed before and is being completed), the slot folders are
+    /// merged into it, replacing the slots that were already there.
+    fn move_range(
+        &self,
+        staging_path: &Path,
+        ready_path: &Path,
+        range: &RangeFolder,
+    ) -> std::io::Result<()> {
+        let (start_slot, end_slot) = (range.start, range.end);
+        metrics().record_committed(end_slot);
+        // The range is committed while saving a later slot, outside of the range.
+        tracing::info!(parent: None, start_slot, end_slot, "Committed");
+        self.last_committed_slot
+            .fetch_max(end_slot.saturating_sub(1), Ordering::Relaxed);
+
+        let from = &staging_path.join(&range.name);
+        let to = &ready_path.join(&range.name);
+        Self::commit_timings(from, to)?;
+        if !Path::exists(to) {
+            return Self::move_folder(staging_path, from, to);
+        }
+        for entry in std::fs::read_dir(from)? {
+            let slot_path = entry?.path();
+            if let Some(folder_name) = slot_path.file_name() {
+                let target_path = to.join(folder_name);
+                if target_path.is_dir() {
+                    std::fs::remove_dir_all(&target_path)?;
                 }
+                std::fs::rename(&slot_path, &target_path)?;
+            }
+        }
+        std::fs::remove_dir(from)?;
+        Self::remove_empty_parents(staging_path, from)
+    }
+
+    /// Renames a range folder, whose path may have several segments, creating the parent
+    /// folders of `to` and removing the parent folders of `from` left empty, up to `root`.
+    pub(crate) fn move_folder(root: &Path, from: &Path, to: &Path) -> std::io::Result<()> {
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::rename(from, to)?;
+        Self::remove_empty_parents(root, from)
+    }
+
+    /// Removes the parent folders of `path` left empty, up to `root`.
+    pub(crate) fn remove_empty_parents(root: &Path, path: &Path) -> std::io::Result<()> {
+        for parent in path.ancestors().skip(1) {
+            if parent == root || !parent.starts_with(root) {
+                break;
+            }
+            if std::fs::read_dir(parent)?.next().is_some() {
+                break;
             }
+            std::fs::remove_dir(parent)?;
         }
         Ok(())
     }
 
+    /// Sets the commit time of the slots of a range about to be moved, and adds the slots
+    /// committed before when the range is merged into an existing one.
+    fn commit_timings(from: &Path, to: &Path) -> std::io::Result<()> {
+        let timings_path = from.join(TIMINGS_FILE_NAME);
+        if !timings_path.is_file() {
+            return Ok(());
+        }
+        let committed = now_millis();
+        let mut timings = SlotTimings::load(&timings_path)?;
+        for slot_timings in timings.iter_mut() {
+            slot_timings.committed = Some(committed);
+            metrics().record_stage("committed", slot_timings, slot_timings.committed);
+        }
+        let previous_path = to.join(TIMINGS_FILE_NAME);
+        if previous_path.is_file() {
+            let slots: HashSet<Slot> = timings.iter().map(|timings| timings.slot).collect();
+            timings.extend(
+                SlotTimings::load(&previous_path)?
+                    .into_iter()
+                    .filter(|timings| !slots.contains(&timings.slot)),
+            );
+        }
+        SlotTimings::save(&timings_path, &mut timings)
+    }
+
     fn extract_memos(
         account_keys: &AccountKeys,
         instructions: &[CompiledInstruction],
@@ -199,46 +828,73 @@ impl StorageManager {
             .collect()
     }
 
+    /// Returns the content hash of each cell, i.e. the hash of its uncompressed protobuf
+    /// encoding, keyed by `<table>/<key>`. Each table has a single cell per slot, whose path
+    /// follows the cell template.
     fn put_protobuf_cells<T>(
         &self,
-        staging_path: &Path,
+        slot_path: &Path,
         slot: Slot,
+        block_time: Option<UnixTimestamp>,
         table_name: &str,
         cells: &[(RowKey, T)],
-    ) -> std::io::Result<()>
+    ) -> std::io::Result<Vec<(String, Hash)>>
     where
         T: prost::Message,
     {
         let mut new_row_data = vec![];
+        let mut content_hashes = vec![];
         for (row_key, data) in cells {
             let mut buf = Vec::with_capacity(data.encoded_len());
             data.encode(&mut buf).unwrap();
-            let data = compress_best(&buf)?;
-            new_row_data.push((row_key, "proto".to_string(), data));
+            content_hashes.push((format!("{table_name}/{row_key}"), hash(&buf)));
+            let data = match self.compression {
+                Some(method) => compress(method, &buf)?,
+                None => compress_best(&buf)?,
+            };
+            metrics().record_cell(compression_method(&data)?, data.len(), buf.len());
+            let cell_name = self.naming.cell(table_name, slot, block_time);
+            new_row_data.push((cell_name, "proto".to_string(), data));
         }
-        self.save_row_data(staging_path, slot, table_name, &new_row_data)
+        Self::save_row_data(slot_path, &new_row_data)?;
+        Ok(content_hashes)
     }
 
-    fn save_row_data(
-        &self,
-        staging_path: &Path,
-        slot: Slot,
-        table_name: &str,
-        row_data: &[(&RowKey, RowType, RowData)],
+    /// Record the content hashes of the cells of a slot in `content_hashes.json`,
+    /// next to the table folders.
+    fn save_content_hashes(
+        folder_path: &Path,
+        content_hashes: &BTreeMap<String, Hash>,
     ) -> std::io::Result<()> {
-        let folder_path = staging_path
-            .join(Self::format_slot_range(slot, self.slot_range))
-            .join(Self::format_slot_single(slot))
-            .join(table_name);
+        let content_hashes: BTreeMap<&String, String> = content_hashes
+            .iter()
+            .map(|(cell, hash)| (cell, hash.to_string()))
+            .collect();
+        let data = serde_json::to_vec_pretty(&content_hashes)?;
+        Self::save_row(folder_path, "content_hashes", "json", &data)
+    }
 
-        // Ensure clean staging directory
-        if Path::exists(&folder_path) {
-            std::fs::remove_dir_all(&folder_path)?;
-        }
-        std::fs::create_dir_all(&folder_path)?;
+    /// Record the transaction counts of a slot in `transaction_counts.json`, next to the
+    /// table folders.
+    fn save_transaction_counts(
+        folder_path: &Path,
+        transaction_counts: &TransactionCounts,
+    ) -> std::io::Result<()> {
+        let data = serde_json::to_vec_pretty(transaction_counts)?;
+        Self::save_row(folder_path, "transaction_counts", "json", &data)
+    }
 
+    /// Writes cells to the slot folder. Their paths may have several segments.
+    fn save_row_data(
+        folder_path: &Path,
+        row_data: &[(RowKey, RowType, RowData)],
+    ) -> std::io::Result<()> {
         for (key, data_type, data) in row_data {
-            Self::save_row(&folder_path, key, data_type, data)?;
+            let file_path = folder_path.join(format!("{key}.{data_type}"));
+            if let Some(parent) = file_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            Self::save_row(folder_path, key, data_type, data)?;
         }
         Ok(())
     }
@@ -255,17 +911,28 @@ impl StorageManager {
             .truncate(true)
             .write(true)
             .open(file_path)?;
-        file.write_all(data)
+        file.write_all(data)?;
+        metrics().bytes_written.inc_by(data.len() as u64);
+        Ok(())
     }
 
-    fn format_slot_range(slot: Slot, slot_range: u64) -> String {
+    /// Name of the folder of the slot range containing `slot`, with the fixed range policy
+    /// and the default templates.
+    pub fn format_slot_range(slot: Slot, slot_range: u64) -> String {
         let start_slot = slot - (slot % slot_range);
+        Self::format_range(start_slot, start_slot + slot_range)
+    }
+
+    /// Name of the folder of the slot range from `start_slot` to `end_slot` (exclusive), with
+    /// the default templates.
+    pub fn format_range(start_slot: Slot, end_slot: Slot) -> String {
         let start_slot_str = Self::format_slot(start_slot);
-        let end_slot_str = Self::format_slot(start_slot + slot_range);
+        let end_slot_str = Self::format_slot(end_slot);
         format!("range_{start_slot_str}_{end_slot_str}")
     }
 
-    fn format_slot_single(slot: Slot) -> String {
+    /// Name of the folder of `slot` inside its slot range folder, with the default templates.
+    pub fn format_slot_single(slot: Slot) -> String {
         let slot_str = Self::format_slot(slot);
         format!("slot_{slot_str}")
     }
@@ -274,11 +941,41 @@ impl StorageManager {
         format!("{slot:016x}")
     }
 
-    fn slot_to_blocks_key(slot: Slot) -> String {
+    /// Inverse of `format_slot_range`, returns the first slot and the end slot (exclusive).
+    pub fn parse_slot_range(folder_name: &str) -> Option<(Slot, Slot)> {
+        let (start_slot_str, end_slot_str) = folder_name.strip_prefix("range_")?.split_once('_')?;
+        Some((
+            Self::parse_slot(start_slot_str)?,
+            Self::parse_slot(end_slot_str)?,
+        ))
+    }
+
+    /// Inverse of `format_slot_single`.
+    pub fn parse_slot_single(folder_name: &str) -> Option<Slot> {
+        Self::parse_slot(folder_name.strip_prefix("slot_")?)
+    }
+
+    fn parse_slot(slot_str: &str) -> Option<Slot> {
+        Slot::from_str_radix(slot_str, 16).ok()
+    }
+
+    /// Row key of the `blocks` cell of `slot`.
+    pub fn slot_to_blocks_key(slot: Slot) -> String {
         Self::slot_to_key(slot)
     }
 
-    fn slot_to_entries_key(slot: Slot) -> String {
+    /// Row key of the `entries` cell of `slot`.
+    pub fn slot_to_entries_key(slot: Slot) -> String {
+        Self::slot_to_key(slot)
+    }
+
+    /// Row key of the `accounts` cell of `slot`.
+    pub fn slot_to_accounts_key(slot: Slot) -> String {
+        Self::slot_to_key(slot)
+    }
+
+    /// Row key of the `votes` cell of `slot`.
+    pub fn slot_to_votes_key(slot: Slot) -> String {
         Self::slot_to_key(slot)
     }
 
@@ -302,3 +999,196 @@ impl ExtractMemos for CosVersionedTransactionWithStatusMeta {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{reader::WorkspaceReader, templates::Naming},
+        serde_json::json,
+        solana_sdk::{hash::Hash, signature::Signature, transaction::VersionedTransaction},
+        solana_transaction_status::VersionedTransactionWithStatusMeta,
+    };
+
+    #[test]
+    fn test_size_ranges_do_not_overlap_committed_ranges() {
+        let workspace =
+            std::env::temp_dir().join(format!("cos-size-ranges-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&workspace);
+        let config: GeyserPluginCosConfig = serde_json::from_value(json!({
+            "workspace": workspace,
+            "genesis_hash": Hash::default().to_string(),
+            "slot_range": 100,
+            "commit_slot_delay": 0,
+            "range_policy": {"type": "size", "target_bytes": 1 << 30},
+        }))
+        .unwrap();
+        let save_slots = |slots: std::ops::RangeInclusive<Slot>| {
+            let storage = StorageManager::new(&config).unwrap();
+            for slot in slots {
+                storage
+                    .save(slot, &CosVersionedConfirmedBlockWithEntries::default())
+                    .unwrap();
+            }
+            storage.commit_all().unwrap();
+        };
+        save_slots(20..=24);
+        // A restart replays slots before, in and after the committed range.
+        save_slots(15..=30);
+
+        let ranges: Vec<_> = Naming::default()
+            .list_ranges(&workspace.join("storage"))
+            .unwrap()
+            .into_iter()
+            .map(|range| (range.start, range.end))
+            .collect();
+        assert_eq!(ranges, vec![(15, 20), (20, 25), (25, 31)]);
+        std::fs::remove_dir_all(workspace).unwrap();
+    }
+
+    #[test]
+    fn test_ranges_keep_the_date_of_their_first_slot() {
+        let workspace =
+            std::env::temp_dir().join(format!("cos-range-dates-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&workspace);
+        let config: GeyserPluginCosConfig = serde_json::from_value(json!({
+            "workspace": workspace,
+            "genesis_hash": Hash::default().to_string(),
+            "slot_range": 10,
+            "commit_slot_delay": 0,
+            "templates": {"range_folder": "{date}/range_{range_start}_{range_end}"},
+        }))
+        .unwrap();
+        let save_slots = |slots: &[(Slot, UnixTimestamp)]| {
+            let storage = StorageManager::new(&config).unwrap();
+            for (slot, block_time) in slots {
+                let mut block = CosVersionedConfirmedBlockWithEntries::default();
+                block.block.block_time = Some(*block_time);
+                storage.save(*slot, &block).unwrap();
+            }
+            storage.commit_all().unwrap();
+        };
+        const DAY: UnixTimestamp = 24 * 60 * 60;
+        save_slots(&[(0, DAY), (1, 2 * DAY), (10, 3 * DAY)]);
+        // A restart adds a slot to the committed range of slot 0, under its name.
+        save_slots(&[(2, 4 * DAY)]);
+
+        let ranges: Vec<_> = config
+            .layout()
+            .naming()
+            .unwrap()
+            .list_ranges(&workspace.join("storage"))
+            .unwrap()
+            .into_iter()
+            .map(|range| (range.name, range.block_time))
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (
+                    "1970-01-02/range_0000000000000000_000000000000000a".to_string(),
+                    Some(DAY)
+                ),
+                (
+                    "1970-01-04/range_000000000000000a_0000000000000014".to_string(),
+                    Some(3 * DAY)
+                ),
+            ]
+        );
+        let range_path = workspace.join("storage").join(&ranges[0].0);
+        for slot in 0..3 {
+            assert!(range_path.join(format!("slot_{slot:016x}")).is_dir());
+        }
+        std::fs::remove_dir_all(workspace).unwrap();
+    }
+
+    #[test]
+    fn test_offline_storage_keeps_staging() {
+        let workspace =
+            std::env::temp_dir().join(format!("cos-offline-storage-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&workspace);
+        let config: GeyserPluginCosConfig = serde_json::from_value(json!({
+            "workspace": workspace,
+            "genesis_hash": Hash::default().to_string(),
+            "slot_range": 10,
+            "commit_slot_delay": 0,
+        }))
+        .unwrap();
+        let storage = StorageManager::new(&config).unwrap();
+        storage
+            .save(5, &CosVersionedConfirmedBlockWithEntries::default())
+            .unwrap();
+        // The workspace is in use.
+        assert!(StorageManager::new_offline(&config).is_err());
+
+        // The slots in staging are not discarded once the plugin stopped.
+        drop(storage);
+        assert!(StorageManager::new_offline(&config).is_err());
+        let staging_path = workspace.join("staging");
+        assert!(std::fs::read_dir(&staging_path).unwrap().next().is_some());
+
+        std::fs::remove_dir_all(&staging_path).unwrap();
+        StorageManager::new_offline(&config).unwrap();
+        std::fs::remove_dir_all(workspace).unwrap();
+    }
+
+    #[test]
+    fn test_transaction_counts() {
+        let workspace =
+            std::env::temp_dir().join(format!("cos-transaction-counts-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&workspace);
+        let config: GeyserPluginCosConfig = serde_json::from_value(json!({
+            "workspace": workspace,
+            "genesis_hash": Hash::default().to_string(),
+            "slot_range": 10,
+            "commit_slot_delay": 0,
+            "transaction_filter": {"exclude_votes": true},
+        }))
+        .unwrap();
+        let transaction = VersionedTransactionWithStatusMeta {
+            transaction: VersionedTransaction {
+                signatures: vec![Signature::new_unique()],
+                message: Default::default(),
+            },
+            meta: Default::default(),
+        };
+        let empty = VersionedTransactionWithStatusMeta {
+            transaction: Default::default(),
+            meta: Default::default(),
+        };
+        // Transaction 1 is filtered, and the notification of transaction 3 was lost.
+        let mut block = CosVersionedConfirmedBlockWithEntries::default();
+        block.block.transactions = vec![
+            transaction.clone(),
+            empty.clone(),
+            transaction.clone(),
+            empty,
+            transaction,
+        ];
+        block.filtered_transaction_indexes = [1].into();
+        block.executed_transaction_count = 5;
+        let storage = StorageManager::new(&config).unwrap();
+        storage.save(12, &block).unwrap();
+        storage.commit_all().unwrap();
+        drop(storage);
+
+        let slot = WorkspaceReader::open(&workspace)
+            .unwrap()
+            .all_slots()
+            .unwrap()
+            .remove(&12)
+            .unwrap();
+        assert_eq!(
+            slot.transaction_counts().unwrap(),
+            Some(TransactionCounts {
+                executed: 5,
+                archived: 3,
+                filtered: 1,
+            })
+        );
+        // The filtered transaction leaves no empty slot behind, the lost one does.
+        let block = WorkspaceReader::read_block(&slot).unwrap().unwrap();
+        assert_eq!(block.transactions.len(), 4);
+        std::fs::remove_dir_all(workspace).unwrap();
+    }
+}
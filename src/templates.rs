@@ -0,0 +1,783 @@
+/// Templates of the range folders, slot folders and cells of a workspace, e.g.
+/// `{cluster}/range_{range_start}_{range_end}`, applied by the writer and parsed back by the
+/// reader.
+use {
+    serde::{Deserialize, Serialize},
+    solana_sdk::{
+        clock::{Epoch, Slot, UnixTimestamp},
+        epoch_schedule::EpochSchedule,
+    },
+    std::{
+        collections::BTreeMap,
+        fs, io,
+        path::{Path, PathBuf},
+    },
+};
+
+/// The templates of the folders and cells, as configured.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyTemplates {
+    /// Path of a range folder in `staging` and `storage`. May contain `/`.
+    #[serde(default = "default_range_folder")]
+    pub range_folder: String,
+    /// Name of a slot folder in its range folder.
+    #[serde(default = "default_slot_folder")]
+    pub slot_folder: String,
+    /// Path of a cell in its slot folder, without the `.proto` extension. May contain `/`.
+    #[serde(default = "default_cell")]
+    pub cell: String,
+    /// Cell templates of specific tables, replacing `cell`.
+    #[serde(default)]
+    pub tables: BTreeMap<String, String>,
+}
+
+fn default_range_folder() -> String {
+    "range_{range_start}_{range_end}".to_string()
+}
+
+fn default_slot_folder() -> String {
+    "slot_{slot}".to_string()
+}
+
+fn default_cell() -> String {
+    "{table}/{slot}".to_string()
+}
+
+impl Default for KeyTemplates {
+    fn default() -> Self {
+        Self {
+            range_folder: default_range_folder(),
+            slot_folder: default_slot_folder(),
+            cell: default_cell(),
+            tables: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Placeholder {
+    /// The configured cluster name.
+    Cluster,
+    /// Identity pubkey of the validator.
+    Identity,
+    Table,
+    /// Hexadecimal slot, on 16 digits.
+    Slot,
+    /// Hexadecimal `!slot`, so that later slots sort first, as in the BigTable
+    /// `tx-by-addr` keys.
+    SlotReversed,
+    /// Hexadecimal first slot of the range.
+    RangeStart,
+    /// Hexadecimal end slot (exclusive) of the range.
+    RangeEnd,
+    /// Epoch of the slot, or of the first slot of the range.
+    Epoch,
+    /// Unix timestamp of the block.
+    BlockTime,
+    /// `YYYY-MM-DD` date of the block, in UTC.
+    Date,
+}
+
+const PLACEHOLDERS: [(&str, Placeholder); 10] = [
+    ("cluster", Placeholder::Cluster),
+    ("identity", Placeholder::Identity),
+    ("table", Placeholder::Table),
+    ("slot", Placeholder::Slot),
+    ("slot_reversed", Placeholder::SlotReversed),
+    ("range_start", Placeholder::RangeStart),
+    ("range_end", Placeholder::RangeEnd),
+    ("epoch", Placeholder::Epoch),
+    ("block_time", Placeholder::BlockTime),
+    ("date", Placeholder::Date),
+];
+
+/// Values of the placeholders, for rendering or parsed from a name.
+#[derive(Clone, Debug, Default)]
+struct Values {
+    cluster: Option<String>,
+    identity: Option<String>,
+    table: Option<String>,
+    slot: Option<Slot>,
+    range_start: Option<Slot>,
+    /// `None` renders `open`, for the ranges of the size policy being filled.
+    range_end: Option<Slot>,
+    epoch: Option<Epoch>,
+    block_time: Option<UnixTimestamp>,
+    /// Parsed from a name only, rendered from `block_time`.
+    date: Option<chrono::NaiveDate>,
+}
+
+impl Values {
+    /// The block time, or the start of the day if only the date is known.
+    fn block_time(&self) -> Option<UnixTimestamp> {
+        self.block_time.or_else(|| {
+            self.date
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map(|time| time.and_utc().timestamp())
+        })
+    }
+}
+
+impl Placeholder {
+    fn render(self, values: &Values) -> String {
+        match self {
+            Self::Cluster => values.cluster.clone().unwrap_or_default(),
+            Self::Identity => values.identity.clone().unwrap_or_default(),
+            Self::Table => values.table.clone().unwrap_or_default(),
+            Self::Slot => format!("{:016x}", values.slot.unwrap_or_default()),
+            Self::SlotReversed => format!("{:016x}", !values.slot.unwrap_or_default()),
+            Self::RangeStart => format!("{:016x}", values.range_start.unwrap_or_default()),
+            Self::RangeEnd => match values.range_end {
+                Some(range_end) => format!("{range_end:016x}"),
+                None => "open".to_string(),
+            },
+            Self::Epoch => values.epoch.unwrap_or_default().to_string(),
+            // Blocks without block time are named as of the Unix epoch.
+            Self::BlockTime => values.block_time.unwrap_or_default().to_string(),
+            Self::Date => {
+                chrono::DateTime::from_timestamp(values.block_time.unwrap_or_default(), 0)
+                    .unwrap_or_default()
+                    .format("%Y-%m-%d")
+                    .to_string()
+            }
+        }
+    }
+
+    /// Parses `value` into `values`. Fails if it is not a valid value, or if the placeholder
+    /// was already parsed with another value.
+    fn parse(self, value: &str, values: &mut Values) -> bool {
+        fn set<T: PartialEq>(field: &mut Option<T>, value: Option<T>) -> bool {
+            match (field.as_ref(), value) {
+                (_, None) => false,
+                (Some(previous), Some(value)) => *previous == value,
+                (None, value) => {
+                    *field = value;
+                    true
+                }
+            }
+        }
+        let hex = || {
+            (value.len() == 16)
+                .then(|| Slot::from_str_radix(value, 16).ok())
+                .flatten()
+        };
+        let decimal = || {
+            value
+                .bytes()
+                .all(|b| b.is_ascii_digit())
+                .then(|| value.parse().ok())
+                .flatten()
+        };
+        match self {
+            Self::Cluster => set(&mut values.cluster, Some(value.to_string())),
+            Self::Identity => set(&mut values.identity, Some(value.to_string())),
+            Self::Table => set(&mut values.table, Some(value.to_string())),
+            Self::Slot => set(&mut values.slot, hex()),
+            Self::SlotReversed => set(&mut values.slot, hex().map(|slot| !slot)),
+            Self::RangeStart => set(&mut values.range_start, hex()),
+            Self::RangeEnd => set(&mut values.range_end, hex()),
+            Self::Epoch => set(&mut values.epoch, decimal()),
+            Self::BlockTime => set(
+                &mut values.block_time,
+                decimal().and_then(|block_time: u64| block_time.try_into().ok()),
+            ),
+            Self::Date => set(
+                &mut values.date,
+                chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").ok(),
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Part {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+#[derive(Clone, Debug)]
+struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parses `template`, which may only use the `allowed` placeholders and must use one of
+    /// the `required` ones, if any.
+    fn parse(
+        template: &str,
+        allowed: &[Placeholder],
+        required: &[Placeholder],
+    ) -> Result<Self, String> {
+        let mut parts = vec![];
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(Part::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed placeholder in {template:?}"))?;
+            let name = &rest[open + 1..open + close];
+            let placeholder = PLACEHOLDERS
+                .iter()
+                .find(|(placeholder_name, _)| *placeholder_name == name)
+                .map(|(_, placeholder)| *placeholder)
+                .filter(|placeholder| allowed.contains(placeholder))
+                .ok_or_else(|| format!("placeholder {{{name}}} is not allowed in {template:?}"))?;
+            parts.push(Part::Placeholder(placeholder));
+            rest = &rest[open + close + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        if parts
+            .iter()
+            .any(|part| matches!(part, Part::Literal(literal) if literal.contains('}')))
+        {
+            return Err(format!("unopened placeholder in {template:?}"));
+        }
+        if template.is_empty()
+            || template
+                .split('/')
+                .any(|segment| segment.is_empty() || segment == "." || segment == "..")
+        {
+            return Err(format!("{template:?} is not a relative path"));
+        }
+        let template = Self { parts };
+        if !required.is_empty()
+            && !required
+                .iter()
+                .any(|placeholder| template.uses(*placeholder))
+        {
+            let names: Vec<String> = PLACEHOLDERS
+                .iter()
+                .filter(|(_, placeholder)| required.contains(placeholder))
+                .map(|(name, _)| format!("{{{name}}}"))
+                .collect();
+            return Err(format!("must use {}", names.join(" or ")));
+        }
+        Ok(template)
+    }
+
+    fn uses(&self, placeholder: Placeholder) -> bool {
+        self.parts
+            .iter()
+            .any(|part| matches!(part, Part::Placeholder(used) if *used == placeholder))
+    }
+
+    /// Number of path segments of the names.
+    fn depth(&self) -> usize {
+        1 + self
+            .parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(literal) => literal.matches('/').count(),
+                Part::Placeholder(_) => 0,
+            })
+            .sum::<usize>()
+    }
+
+    fn render(&self, values: &Values) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(literal) => literal.clone(),
+                Part::Placeholder(placeholder) => placeholder.render(values),
+            })
+            .collect()
+    }
+
+    /// Values of the placeholders of `name`, if it matches the template.
+    fn parse_name(&self, name: &str) -> Option<Values> {
+        let mut values = Values::default();
+        Self::match_parts(&self.parts, name, &mut values).then_some(values)
+    }
+
+    fn match_parts(parts: &[Part], name: &str, values: &mut Values) -> bool {
+        match parts.split_first() {
+            None => name.is_empty(),
+            Some((Part::Literal(literal), parts)) => name
+                .strip_prefix(literal.as_str())
+                .is_some_and(|name| Self::match_parts(parts, name, values)),
+            Some((Part::Placeholder(placeholder), parts)) => {
+                // Values never span several path segments.
+                let max_len = name.find('/').unwrap_or(name.len());
+                (1..=max_len)
+                    .filter(|len| name.is_char_boundary(*len))
+                    .any(|len| {
+                        let mut candidate = values.clone();
+                        if placeholder.parse(&name[..len], &mut candidate)
+                            && Self::match_parts(parts, &name[len..], &mut candidate)
+                        {
+                            *values = candidate;
+                            true
+                        } else {
+                            false
+                        }
+                    })
+            }
+        }
+    }
+}
+
+/// The names of the folders and cells of a workspace, from its templates.
+#[derive(Clone, Debug)]
+pub struct Naming {
+    range_folder: Template,
+    slot_folder: Template,
+    cell: Template,
+    tables: BTreeMap<String, Template>,
+    cluster: Option<String>,
+    identity: Option<String>,
+    /// Epoch schedule of the `{epoch}` placeholder.
+    epoch_schedule: EpochSchedule,
+}
+
+impl Default for Naming {
+    fn default() -> Self {
+        Self::new(
+            &KeyTemplates::default(),
+            None,
+            None,
+            EpochSchedule::without_warmup(),
+        )
+        .expect("valid default templates")
+    }
+}
+
+/// A range folder found in a workspace.
+#[derive(Clone, Debug)]
+pub struct RangeFolder {
+    pub start: Slot,
+    /// End slot (exclusive).
+    pub end: Slot,
+    /// Path relative to `staging` or `storage`, with `/` separators.
+    pub name: String,
+    /// Block time the range is named after, if the template uses `{block_time}` or `{date}`.
+    /// Only the day is known from `{date}`.
+    pub block_time: Option<UnixTimestamp>,
+}
+
+impl Naming {
+    /// Parses the templates. Errors name the invalid template, e.g. `templates.cell`.
+    pub fn new(
+        templates: &KeyTemplates,
+        cluster: Option<&str>,
+        identity: Option<&str>,
+        epoch_schedule: EpochSchedule,
+    ) -> Result<Self, (String, String)> {
+        use Placeholder::*;
+        let field = |name: &str| {
+            let field = format!("templates.{name}");
+            move |msg| (field, msg)
+        };
+        let range_folder = Template::parse(
+            &templates.range_folder,
+            &[
+                Cluster, Identity, RangeStart, RangeEnd, Epoch, BlockTime, Date,
+            ],
+            &[],
+        )
+        .map_err(field("range_folder"))?;
+        if !range_folder.uses(RangeStart) || !range_folder.uses(RangeEnd) {
+            return Err((
+                "templates.range_folder".to_string(),
+                "must use {range_start} and {range_end}".to_string(),
+            ));
+        }
+        let slot_folder = Template::parse(
+            &templates.slot_folder,
+            &[Cluster, Identity, Slot, SlotReversed, Epoch],
+            &[Slot, SlotReversed],
+        )
+        .map_err(field("slot_folder"))?;
+        if slot_folder.depth() > 1 {
+            return Err((
+                "templates.slot_folder".to_string(),
+                "must not contain /".to_string(),
+            ));
+        }
+        let cell_placeholders = [
+            Cluster,
+            Identity,
+            Table,
+            Slot,
+            SlotReversed,
+            Epoch,
+            BlockTime,
+            Date,
+        ];
+        let cell = Template::parse(&templates.cell, &cell_placeholders, &[Table])
+            .map_err(field("cell"))?;
+        let mut tables = BTreeMap::new();
+        for (table, template) in &templates.tables {
+            let template = Template::parse(template, &cell_placeholders, &[])
+                .map_err(field(&format!("tables.{table}")))?;
+            tables.insert(table.clone(), template);
+        }
+        for (field, placeholder, value) in [
+            ("cluster", Cluster, cluster),
+            ("identity", Identity, identity),
+        ] {
+            let templates_use_placeholder = [&range_folder, &slot_folder, &cell]
+                .into_iter()
+                .chain(tables.values())
+                .any(|template| template.uses(placeholder));
+            if templates_use_placeholder && value.map_or(true, str::is_empty) {
+                return Err((
+                    field.to_string(),
+                    format!("must be set, as the templates use {{{field}}}"),
+                ));
+            }
+        }
+        Ok(Self {
+            range_folder,
+            slot_folder,
+            cell,
+            tables,
+            cluster: cluster.map(str::to_string),
+            identity: identity.map(str::to_string),
+            epoch_schedule,
+        })
+    }
+
+    /// Whether the names depend on the identity of the validator.
+    pub fn uses_identity(&self) -> bool {
+        [&self.range_folder, &self.slot_folder, &self.cell]
+            .into_iter()
+            .chain(self.tables.values())
+            .any(|template| template.uses(Placeholder::Identity))
+    }
+
+    /// Whether the range folders are named after the block time of the range, i.e. of the
+    /// first slot written to it.
+    pub fn range_uses_block_time(&self) -> bool {
+        self.range_folder.uses(Placeholder::BlockTime) || self.range_folder.uses(Placeholder::Date)
+    }
+
+    /// Path of the range folder from `start` to `end` (exclusive), or of the range of the
+    /// size policy being filled if `end` is `None`. `block_time` is the block time of the
+    /// range, if the template uses it.
+    pub fn range_folder(
+        &self,
+        start: Slot,
+        end: Option<Slot>,
+        block_time: Option<UnixTimestamp>,
+    ) -> String {
+        self.range_folder.render(&Values {
+            cluster: self.cluster.clone(),
+            identity: self.identity.clone(),
+            range_start: Some(start),
+            range_end: end,
+            epoch: Some(self.epoch_schedule.get_epoch(start)),
+            block_time,
+            ..Default::default()
+        })
+    }
+
+    /// First slot and end slot (exclusive) of a range folder path, with `/` separators.
+    pub fn parse_range_folder(&self, name: &str) -> Option<(Slot, Slot)> {
+        let values = self.range_folder.parse_name(name)?;
+        Some((values.range_start?, values.range_end?))
+    }
+
+    /// Name of the folder of `slot` in its range folder.
+    pub fn slot_folder(&self, slot: Slot) -> String {
+        self.slot_folder.render(&Values {
+            cluster: self.cluster.clone(),
+            identity: self.identity.clone(),
+            slot: Some(slot),
+            epoch: Some(self.epoch_schedule.get_epoch(slot)),
+            ..Default::default()
+        })
+    }
+
+    /// Slot of a slot folder name.
+    pub fn parse_slot_folder(&self, name: &str) -> Option<Slot> {
+        self.slot_folder.parse_name(name)?.slot
+    }
+
+    /// Path of the cell of `table` in the slot folder, without the `.proto` extension.
+    pub fn cell(&self, table: &str, slot: Slot, block_time: Option<UnixTimestamp>) -> String {
+        self.tables
+            .get(table)
+            .unwrap_or(&self.cell)
+            .render(&Values {
+                cluster: self.cluster.clone(),
+                identity: self.identity.clone(),
+                table: Some(table.to_string()),
+                slot: Some(slot),
+                epoch: Some(self.epoch_schedule.get_epoch(slot)),
+                block_time,
+                ..Default::default()
+            })
+    }
+
+    /// Table of a cell path in its slot folder, with `/` separators and without extension.
+    /// The templates of specific tables are tried first.
+    pub fn parse_cell(&self, name: &str) -> Option<String> {
+        for (table, template) in &self.tables {
+            if template.parse_name(name).is_some() {
+                return Some(table.clone());
+            }
+        }
+        self.cell
+            .parse_name(name)?
+            .table
+            .filter(|table| !self.tables.contains_key(table))
+    }
+
+    /// The range folders in `root`, e.g. the `storage` folder, ordered by first slot.
+    pub fn list_ranges(&self, root: &Path) -> io::Result<Vec<RangeFolder>> {
+        let mut ranges = vec![];
+        for name in list_paths(root, self.range_folder.depth(), true)? {
+            let Some(values) = self.range_folder.parse_name(&name) else {
+                continue;
+            };
+            if let (Some(start), Some(end)) = (values.range_start, values.range_end) {
+                ranges.push(RangeFolder {
+                    start,
+                    end,
+                    block_time: values.block_time(),
+                    name,
+                });
+            }
+        }
+        ranges.sort_by_key(|range| (range.start, range.end));
+        Ok(ranges)
+    }
+
+    /// The cells in a slot folder, by table.
+    pub fn list_cells(&self, slot_path: &Path) -> io::Result<BTreeMap<String, PathBuf>> {
+        let depths = std::iter::once(&self.cell)
+            .chain(self.tables.values())
+            .map(Template::depth);
+        let mut cells = BTreeMap::new();
+        let mut seen_depths = vec![];
+        for depth in depths {
+            if seen_depths.contains(&depth) {
+                continue;
+            }
+            seen_depths.push(depth);
+            for name in list_paths(slot_path, depth, false)? {
+                let Some(key) = name.strip_suffix(".proto") else {
+                    continue;
+                };
+                if let Some(table) = self.parse_cell(key) {
+                    cells.insert(table, slot_path.join(&name));
+                }
+            }
+        }
+        Ok(cells)
+    }
+}
+
+/// Paths of the folders, or of the files, `depth` levels below `root`, relative to `root`
+/// with `/` separators.
+fn list_paths(root: &Path, depth: usize, folders: bool) -> io::Result<Vec<String>> {
+    let mut paths = vec![];
+    if !root.is_dir() {
+        return Ok(paths);
+    }
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let is_dir = entry.file_type()?.is_dir();
+        if depth > 1 {
+            if is_dir {
+                for path in list_paths(&entry.path(), depth - 1, folders)? {
+                    paths.push(format!("{name}/{path}"));
+                }
+            }
+        } else if is_dir == folders {
+            paths.push(name);
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json};
+
+    const CLUSTER: &str = "mainnet-beta";
+    const IDENTITY: &str = "Validator1111";
+
+    fn naming(templates: serde_json::Value) -> Result<Naming, (String, String)> {
+        Naming::new(
+            &serde_json::from_value(templates).unwrap(),
+            Some(CLUSTER),
+            Some(IDENTITY),
+            EpochSchedule::without_warmup(),
+        )
+    }
+
+    fn error(templates: serde_json::Value) -> (String, String) {
+        naming(templates).unwrap_err()
+    }
+
+    #[test]
+    fn test_invalid_templates_are_rejected() {
+        assert_eq!(
+            error(json!({"cell": "{table}/{height}"})),
+            (
+                "templates.cell".to_string(),
+                r#"placeholder {height} is not allowed in "{table}/{height}""#.to_string()
+            )
+        );
+        // Placeholders are only allowed where they are known.
+        assert_eq!(
+            error(json!({"range_folder": "{slot}_{range_start}_{range_end}"})).0,
+            "templates.range_folder"
+        );
+        assert_eq!(
+            error(json!({"slot_folder": "slot_{slot"})).1,
+            r#"unclosed placeholder in "slot_{slot""#
+        );
+        assert_eq!(
+            error(json!({"slot_folder": "slot}_{slot}"})).1,
+            r#"unopened placeholder in "slot}_{slot}""#
+        );
+        assert_eq!(
+            error(json!({"cell": "{table}/../{slot}"})).1,
+            r#""{table}/../{slot}" is not a relative path"#
+        );
+        assert_eq!(
+            error(json!({"tables": {"votes": "/votes_{slot}"}})),
+            (
+                "templates.tables.votes".to_string(),
+                r#""/votes_{slot}" is not a relative path"#.to_string()
+            )
+        );
+
+        // Required placeholders.
+        assert_eq!(
+            error(json!({"range_folder": "range_{range_start}"})),
+            (
+                "templates.range_folder".to_string(),
+                "must use {range_start} and {range_end}".to_string()
+            )
+        );
+        assert_eq!(
+            error(json!({"slot_folder": "slot"})),
+            (
+                "templates.slot_folder".to_string(),
+                "must use {slot} or {slot_reversed}".to_string()
+            )
+        );
+        assert_eq!(
+            error(json!({"slot_folder": "slot/{slot}"})).1,
+            "must not contain /"
+        );
+        assert_eq!(
+            error(json!({"cell": "cells/{slot}"})),
+            ("templates.cell".to_string(), "must use {table}".to_string())
+        );
+
+        let templates = json!({"range_folder": "{cluster}/range_{range_start}_{range_end}"});
+        let err = Naming::new(
+            &serde_json::from_value(templates).unwrap(),
+            None,
+            None,
+            EpochSchedule::without_warmup(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            (
+                "cluster".to_string(),
+                "must be set, as the templates use {cluster}".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_every_placeholder_round_trips() {
+        let naming = naming(json!({
+            "range_folder":
+                "{cluster}/{identity}/{epoch}/{date}/{block_time}_{range_start}_{range_end}",
+            "slot_folder": "{cluster}_{identity}_{epoch}_{slot}_{slot_reversed}",
+            "cell": "{cluster}/{identity}/{table}/{epoch}/{date}/{block_time}_{slot}",
+            "tables": {"votes": "votes/{slot_reversed}"},
+        }))
+        .unwrap();
+        let slot = 432_000 * 3 + 5;
+        let block_time = 1_700_000_000;
+
+        let range_folder = naming.range_folder(slot, Some(slot + 10), Some(block_time));
+        assert_eq!(
+            range_folder,
+            format!(
+                "{CLUSTER}/{IDENTITY}/3/2023-11-14/{block_time}_{slot:016x}_{:016x}",
+                slot + 10
+            )
+        );
+        assert_eq!(
+            naming.parse_range_folder(&range_folder),
+            Some((slot, slot + 10))
+        );
+        assert_eq!(
+            naming.parse_range_folder(&naming.range_folder(slot, None, Some(block_time))),
+            None
+        );
+
+        let slot_folder = naming.slot_folder(slot);
+        assert_eq!(
+            slot_folder,
+            format!("{CLUSTER}_{IDENTITY}_3_{slot:016x}_{:016x}", !slot)
+        );
+        assert_eq!(naming.parse_slot_folder(&slot_folder), Some(slot));
+
+        for table in ["blocks", "entries", "votes"] {
+            let cell = naming.cell(table, slot, Some(block_time));
+            assert_eq!(naming.parse_cell(&cell).as_deref(), Some(table), "{cell}");
+        }
+        assert_eq!(
+            naming.cell("votes", slot, None),
+            format!("votes/{:016x}", !slot)
+        );
+        // The cells of a table with its own template are not parsed by the generic one.
+        assert_eq!(
+            naming.parse_cell(&format!(
+                "{CLUSTER}/{IDENTITY}/votes/3/2023-11-14/{block_time}_{slot:016x}"
+            )),
+            None
+        );
+
+        let root = std::env::temp_dir().join(format!("cos-templates-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join(&range_folder)).unwrap();
+        let ranges = naming.list_ranges(&root).unwrap();
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(
+            (ranges[0].start, ranges[0].end, ranges[0].block_time),
+            (slot, slot + 10, Some(block_time))
+        );
+        assert_eq!(ranges[0].name, range_folder);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_slot_reversed() {
+        let reversed = naming(json!({"slot_folder": "{slot_reversed}"})).unwrap();
+        assert_eq!(reversed.slot_folder(5), "fffffffffffffffa");
+        assert_eq!(reversed.parse_slot_folder("fffffffffffffffa"), Some(5));
+        // Later slots sort first.
+        assert!(reversed.slot_folder(6) < reversed.slot_folder(5));
+        assert_eq!(reversed.parse_slot_folder("fffffffffffffa"), None);
+
+        // Both placeholders must agree on the slot.
+        let both = naming(json!({"slot_folder": "{slot}_{slot_reversed}"})).unwrap();
+        assert_eq!(
+            both.parse_slot_folder(&format!("{:016x}_{:016x}", 5, !5u64)),
+            Some(5)
+        );
+        assert_eq!(
+            both.parse_slot_folder(&format!("{:016x}_{:016x}", 5, !6u64)),
+            None
+        );
+    }
+}
@@ -0,0 +1,176 @@
+/// Times at which each slot went through the plugin, recorded in a `timings.jsonl` file in
+/// each slot range for latency analysis.
+use {
+    serde::{Deserialize, Serialize},
+    solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+    solana_sdk::clock::Slot,
+    std::{
+        fs::{self, OpenOptions},
+        io::{self, Write},
+        path::Path,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+pub const TIMINGS_FILE_NAME: &str = "timings.jsonl";
+
+/// Times of a slot, in milliseconds since the Unix epoch. Stored as one JSON line per slot.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlotTimings {
+    pub slot: Slot,
+    /// First notification of any kind for the slot.
+    pub first_seen: Option<u64>,
+    pub processed: Option<u64>,
+    pub confirmed: Option<u64>,
+    pub rooted: Option<u64>,
+    /// Saved to staging.
+    pub staged: Option<u64>,
+    /// Moved to storage with its range.
+    pub committed: Option<u64>,
+}
+
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+impl SlotTimings {
+    pub(crate) fn new(slot: Slot) -> Self {
+        Self {
+            slot,
+            first_seen: Some(now_millis()),
+            ..Default::default()
+        }
+    }
+
+    /// Records the first time the slot reached `status`.
+    pub(crate) fn record_status(&mut self, status: SlotStatus) {
+        let time = match status {
+            SlotStatus::Processed => &mut self.processed,
+            SlotStatus::Confirmed => &mut self.confirmed,
+            SlotStatus::Rooted => &mut self.rooted,
+        };
+        time.get_or_insert_with(now_millis);
+    }
+
+    /// Milliseconds from the processed status, or from the first notification when the slot
+    /// was not notified as processed, to `time`.
+    pub fn since_processed(&self, time: Option<u64>) -> Option<u64> {
+        let start = self.processed.or(self.first_seen)?;
+        Some(time?.saturating_sub(start))
+    }
+
+    /// Reads the timings file of a slot range.
+    pub fn load(path: &Path) -> io::Result<Vec<SlotTimings>> {
+        fs::read_to_string(path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(io::Error::from))
+            .collect()
+    }
+
+    /// Appends the timings of one slot to a timings file.
+    pub(crate) fn append(&self, path: &Path) -> io::Result<()> {
+        let mut line = serde_json::to_vec(self)?;
+        line.push(b'\n');
+        // A single write per line, as slots of the same range are saved concurrently.
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(&line)
+    }
+
+    /// Replaces a timings file, ordering the slots.
+    pub(crate) fn save(path: &Path, timings: &mut [SlotTimings]) -> io::Result<()> {
+        timings.sort_by_key(|timings| timings.slot);
+        let mut lines = vec![];
+        for slot_timings in timings.iter() {
+            serde_json::to_writer(&mut lines, slot_timings)?;
+            lines.push(b'\n');
+        }
+        fs::write(path, lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            cos_types::CosVersionedConfirmedBlockWithEntries,
+            geyser_plugin_cos_config::GeyserPluginCosConfig,
+            storage::{Storage, StorageManager},
+        },
+        serde_json::{json, Value},
+        solana_sdk::hash::Hash,
+    };
+
+    #[test]
+    fn test_timings_lines() {
+        let workspace = std::env::temp_dir().join(format!("cos-timings-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&workspace);
+        let config: GeyserPluginCosConfig = serde_json::from_value(json!({
+            "workspace": workspace,
+            "genesis_hash": Hash::default().to_string(),
+            "slot_range": 10,
+            "commit_slot_delay": 5,
+        }))
+        .unwrap();
+        let storage = StorageManager::new(&config).unwrap();
+        for slot in [13, 12] {
+            let mut block = CosVersionedConfirmedBlockWithEntries {
+                timings: SlotTimings::new(slot),
+                ..Default::default()
+            };
+            block.timings.record_status(SlotStatus::Processed);
+            block.timings.record_status(SlotStatus::Rooted);
+            storage.save(slot, &block).unwrap();
+        }
+        // Slots rebuilt from another source have no timings.
+        storage
+            .save(14, &CosVersionedConfirmedBlockWithEntries::default())
+            .unwrap();
+        storage.commit_all().unwrap();
+
+        let path = workspace.join("storage/range_000000000000000a_0000000000000014/timings.jsonl");
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.ends_with('\n'));
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(lines.len(), 2, "{content}");
+        for (line, slot) in lines.iter().zip([12, 13]) {
+            assert!(
+                line.starts_with(&format!("{{\"slot\":{slot},\"first_seen\":")),
+                "{line}"
+            );
+            let fields: Value = serde_json::from_str(line).unwrap();
+            let names: Vec<_> = fields.as_object().unwrap().keys().cloned().collect();
+            assert_eq!(
+                names,
+                [
+                    "committed",
+                    "confirmed",
+                    "first_seen",
+                    "processed",
+                    "rooted",
+                    "slot",
+                    "staged"
+                ]
+            );
+            assert_eq!(fields["confirmed"], Value::Null);
+            let time = |name: &str| fields[name].as_u64().unwrap();
+            assert!(time("first_seen") <= time("processed"));
+            assert!(time("processed") <= time("rooted"));
+            assert!(time("rooted") <= time("staged"));
+            assert!(time("staged") <= time("committed"));
+        }
+        let timings = SlotTimings::load(&path).unwrap();
+        assert_eq!(
+            timings[0].since_processed(timings[0].committed),
+            Some(timings[0].committed.unwrap() - timings[0].processed.unwrap())
+        );
+        fs::remove_dir_all(workspace).unwrap();
+    }
+}
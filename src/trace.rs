@@ -0,0 +1,85 @@
+/// Structured traces of the slot lifecycle, written as JSON lines to a dedicated file,
+/// separate from the validator log.
+use {
+    crate::geyser_plugin_cos_config::TracingConfig,
+    solana_geyser_plugin_interface::geyser_plugin_interface::{GeyserPluginError, Result},
+    std::{
+        fs::{File, OpenOptions},
+        io::{self, Write},
+        sync::{Mutex, OnceLock},
+    },
+    tracing_subscriber::{
+        filter::Targets, fmt::format::FmtSpan, layer::SubscriberExt, reload, Registry,
+    },
+};
+
+/// Destination of the traces, replaced when the plugin is loaded again.
+static TRACE_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+/// Filter of the subscriber, installed by the first configuration with traces.
+static FILTER: OnceLock<reload::Handle<Targets, Registry>> = OnceLock::new();
+
+struct TraceFileWriter;
+
+impl Write for TraceFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match TRACE_FILE.lock().unwrap().as_mut() {
+            Some(file) => file.write(buf),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match TRACE_FILE.lock().unwrap().as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Writes the traces to the configured file, or disables them if `config` is `None`.
+pub(crate) fn configure(config: Option<&TracingConfig>) -> Result<()> {
+    let Some(config) = config else {
+        if let Some(handle) = FILTER.get() {
+            let _ = handle.reload(Targets::new());
+        }
+        *TRACE_FILE.lock().unwrap() = None;
+        return Ok(());
+    };
+
+    let filter =
+        config
+            .filter
+            .parse::<Targets>()
+            .map_err(|err| GeyserPluginError::ConfigFileReadError {
+                msg: format!("COS: Invalid tracing filter {}: {err}", config.filter),
+            })?;
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&config.path)?;
+    *TRACE_FILE.lock().unwrap() = Some(file);
+
+    match FILTER.get() {
+        Some(handle) => handle
+            .reload(filter)
+            .map_err(|err| GeyserPluginError::Custom(Box::new(err)))?,
+        None => {
+            let (filter, handle) = reload::Layer::new(filter);
+            let subscriber = Registry::default().with(filter).with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_span_events(FmtSpan::CLOSE)
+                    .with_span_list(false)
+                    .with_writer(|| TraceFileWriter),
+            );
+            if tracing::subscriber::set_global_default(subscriber).is_ok() {
+                let _ = FILTER.set(handle);
+            } else {
+                log::warn!("COS: A tracing subscriber is already installed");
+            }
+        }
+    }
+    log::info!("COS: Writing traces to {}", config.path);
+    Ok(())
+}
@@ -0,0 +1,117 @@
+/// Vote transactions stored in the `votes` table, apart from the `blocks` cell.
+use {
+    solana_sdk::{message::VersionedMessage, transaction::VersionedTransaction, vote},
+    solana_storage_proto::convert::generated,
+};
+
+/// A vote transaction and its position in the full block.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct VoteTransaction {
+    /// Index of the transaction in the transactions of the full block.
+    #[prost(uint64, tag = "1")]
+    pub position: u64,
+    #[prost(message, optional, tag = "2")]
+    pub transaction: Option<generated::ConfirmedTransaction>,
+}
+
+/// The `votes` table cell of a slot.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct VoteTransactions {
+    /// Vote transactions, by increasing position.
+    #[prost(message, repeated, tag = "1")]
+    pub transactions: Vec<VoteTransaction>,
+}
+
+/// Inserts the vote transactions back into a `blocks` cell stored without them.
+pub fn merge_votes(block: &mut generated::ConfirmedBlock, votes: VoteTransactions) {
+    for vote in votes.transactions {
+        let position = (vote.position as usize).min(block.transactions.len());
+        block
+            .transactions
+            .insert(position, vote.transaction.unwrap_or_default());
+    }
+}
+
+/// Returns true for the transactions the runtime considers simple vote transactions, for
+/// sources that don't flag them.
+///
+/// Note that this mirrors the check of the runtime and should be kept in sync with it.
+pub fn is_simple_vote_transaction(transaction: &VersionedTransaction) -> bool {
+    let VersionedMessage::Legacy(message) = &transaction.message else {
+        return false;
+    };
+    transaction.signatures.len() < 3
+        && message.instructions.len() == 1
+        && message
+            .account_keys
+            .get(message.instructions[0].program_id_index as usize)
+            .is_some_and(|program_id| *program_id == vote::program::id())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::{
+            hash::Hash,
+            message::v0,
+            pubkey::Pubkey,
+            signature::Signature,
+            system_instruction,
+            transaction::Transaction,
+            vote::{instruction::vote, state::Vote},
+        },
+    };
+
+    fn transaction(
+        instructions: &[solana_sdk::instruction::Instruction],
+        signatures: usize,
+    ) -> VersionedTransaction {
+        let payer = Pubkey::new_unique();
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&payer));
+        transaction.signatures = vec![Signature::default(); signatures];
+        transaction.into()
+    }
+
+    #[test]
+    fn test_simple_vote_transactions() {
+        let authority = Pubkey::new_unique();
+        let vote_instruction = vote(
+            &Pubkey::new_unique(),
+            &authority,
+            Vote::new(vec![1], Hash::default()),
+        );
+        let transfer = system_instruction::transfer(&authority, &Pubkey::new_unique(), 1);
+
+        assert!(is_simple_vote_transaction(&transaction(
+            &[vote_instruction.clone()],
+            1
+        )));
+        assert!(is_simple_vote_transaction(&transaction(
+            &[vote_instruction.clone()],
+            2
+        )));
+        assert!(!is_simple_vote_transaction(&transaction(
+            &[transfer.clone()],
+            1
+        )));
+        // Votes with another instruction or too many signatures are regular transactions.
+        assert!(!is_simple_vote_transaction(&transaction(
+            &[vote_instruction.clone(), transfer],
+            1
+        )));
+        assert!(!is_simple_vote_transaction(&transaction(
+            &[vote_instruction.clone()],
+            3
+        )));
+        // So are the votes of a v0 message.
+        let message =
+            v0::Message::try_compile(&authority, &[vote_instruction], &[], Hash::default())
+                .unwrap();
+        let v0_vote = VersionedTransaction {
+            signatures: vec![Signature::default()],
+            message: VersionedMessage::V0(message),
+        };
+        assert!(!is_simple_vote_transaction(&v0_vote));
+    }
+}
@@ -0,0 +1,220 @@
+/// Detects a stalled notification stream, when the validator stops sending one kind of event.
+use {
+    crate::{
+        geyser_plugin_cos_config::WatchdogConfig, metrics::metrics, periodic::PeriodicTask,
+        status::EventStatus,
+    },
+    enum_iterator::{all, Sequence},
+    std::{
+        fmt, io,
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    },
+};
+
+/// Interval between two checks of the watchdog.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+pub(crate) enum EventKind {
+    SlotStatus,
+    Rooted,
+    Transaction,
+    Entry,
+    BlockMetadata,
+}
+
+impl EventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::SlotStatus => "slot_status",
+            EventKind::Rooted => "rooted",
+            EventKind::Transaction => "transaction",
+            EventKind::Entry => "entry",
+            EventKind::BlockMetadata => "block_metadata",
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Time of the last event of each kind. Monitoring starts with the end of startup or the
+/// first event, so the time spent loading the snapshot is not reported as a stall.
+#[derive(Default)]
+pub(crate) struct Watchdog {
+    base: Option<Instant>,
+    /// Milliseconds from `base` to the start of monitoring plus one, or 0 before.
+    started: AtomicU64,
+    /// Milliseconds from `base` to the last event plus one, or 0 if there was none.
+    last_seen: [AtomicU64; 5],
+    stalled: [AtomicBool; 5],
+    thresholds: [Duration; 5],
+}
+
+impl Watchdog {
+    pub fn new(config: &WatchdogConfig) -> Self {
+        let mut thresholds = [Duration::ZERO; 5];
+        for kind in all::<EventKind>() {
+            thresholds[kind as usize] = Duration::from_secs(match kind {
+                EventKind::SlotStatus => config.slot_status_secs,
+                EventKind::Rooted => config.rooted_secs,
+                EventKind::Transaction => config.transaction_secs,
+                EventKind::Entry => config.entry_secs,
+                EventKind::BlockMetadata => config.block_metadata_secs,
+            });
+        }
+        Self {
+            base: Some(Instant::now()),
+            thresholds,
+            ..Default::default()
+        }
+    }
+
+    /// Whether at least one kind of event is monitored.
+    pub fn is_enabled(&self) -> bool {
+        self.thresholds.iter().any(|threshold| !threshold.is_zero())
+    }
+
+    fn now(&self) -> u64 {
+        self.base
+            .map(|base| base.elapsed().as_millis() as u64 + 1)
+            .unwrap_or_default()
+    }
+
+    /// Starts monitoring, if it has not started yet.
+    pub fn start(&self) {
+        let _ = self
+            .started
+            .compare_exchange(0, self.now(), Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    pub fn record(&self, kind: EventKind) {
+        let now = self.now();
+        if self.started.load(Ordering::Relaxed) == 0 {
+            self.start();
+        }
+        self.last_seen[kind as usize].store(now, Ordering::Relaxed);
+    }
+
+    /// Time since the last event of `kind`, or since the start of monitoring if there was none.
+    fn since_last(&self, kind: EventKind) -> Option<Duration> {
+        let started = self.started.load(Ordering::Relaxed);
+        if started == 0 {
+            return None;
+        }
+        let last = self.last_seen[kind as usize]
+            .load(Ordering::Relaxed)
+            .max(started);
+        Some(Duration::from_millis(self.now().saturating_sub(last)))
+    }
+
+    /// Updates the stalled state of each kind of event, logging the changes.
+    fn check(&self) {
+        for kind in all::<EventKind>() {
+            let threshold = self.thresholds[kind as usize];
+            let Some(since_last) = self.since_last(kind) else {
+                continue;
+            };
+            metrics().record_event_age(kind, since_last);
+            if threshold.is_zero() {
+                continue;
+            }
+            let stalled = since_last > threshold;
+            if self.stalled[kind as usize].swap(stalled, Ordering::Relaxed) != stalled {
+                if stalled {
+                    log::warn!(
+                        "COS: No {kind} notification received for {}s",
+                        since_last.as_secs()
+                    );
+                } else {
+                    log::info!("COS: Receiving {kind} notifications again");
+                }
+                metrics().record_stalled(kind, stalled);
+            }
+        }
+    }
+
+    pub fn events(&self) -> Vec<EventStatus> {
+        all::<EventKind>()
+            .map(|kind| EventStatus {
+                event: kind.name().to_string(),
+                seconds_since_last: self.since_last(kind).map(|since| since.as_secs()),
+                threshold_secs: self.thresholds[kind as usize].as_secs(),
+                stalled: self.stalled[kind as usize].load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+/// Checks the watchdog every second on a background thread, until dropped.
+pub(crate) struct WatchdogMonitor {
+    _task: PeriodicTask,
+}
+
+impl WatchdogMonitor {
+    pub fn start(watchdog: Arc<Watchdog>) -> io::Result<Self> {
+        let task = PeriodicTask::start("cosWatchdog", CHECK_INTERVAL, move || watchdog.check())?;
+        Ok(Self { _task: task })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json};
+
+    fn rooted_status(watchdog: &Watchdog) -> EventStatus {
+        watchdog
+            .events()
+            .into_iter()
+            .find(|status| status.event == "rooted")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_stalled_roots_are_reported() {
+        let config: WatchdogConfig = serde_json::from_value(json!({
+            "slot_status_secs": 0,
+            "rooted_secs": 2,
+            "transaction_secs": 0,
+            "entry_secs": 0,
+            "block_metadata_secs": 0,
+        }))
+        .unwrap();
+        let mut watchdog = Watchdog::new(&config);
+        assert!(watchdog.is_enabled());
+        // Nothing is reported before the first event.
+        watchdog.check();
+        assert_eq!(rooted_status(&watchdog).seconds_since_last, None);
+
+        watchdog.record(EventKind::SlotStatus);
+        watchdog.record(EventKind::Rooted);
+        watchdog.check();
+        assert!(!rooted_status(&watchdog).stalled);
+
+        // 3 seconds later, slots are still notified but none is rooted.
+        watchdog.base = watchdog.base.unwrap().checked_sub(Duration::from_secs(3));
+        watchdog.record(EventKind::SlotStatus);
+        watchdog.check();
+        let status = rooted_status(&watchdog);
+        assert!(status.stalled);
+        assert_eq!(
+            (status.seconds_since_last, status.threshold_secs),
+            (Some(3), 2)
+        );
+        assert!(watchdog
+            .events()
+            .iter()
+            .all(|status| status.stalled == (status.event == "rooted")));
+
+        watchdog.record(EventKind::Rooted);
+        watchdog.check();
+        assert!(!rooted_status(&watchdog).stalled);
+    }
+}